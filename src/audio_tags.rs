@@ -0,0 +1,128 @@
+use crate::track_metadata::TrackMetadata;
+
+// Extensions the decoder is built to read (see the symphonia features
+// in Cargo.toml), used when scanning folders.
+pub const AUDIO_EXTENSIONS: [&str; 10] = ["mp3", "flac", "m4a", "mp4", "aac", "wav", "ogg", "oga", "aif", "aiff"];
+
+/**
+*
+* Tags and length read straight from an audio file (ID3, Vorbis comments, MP4 atoms).
+*
+*/
+#[derive(Debug, Default, Clone)]
+pub struct AudioTags {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub bpm: String,
+    pub key: String,
+    pub genre: String,
+    pub comment: String,
+    pub duration: f32,
+}
+
+impl AudioTags {
+    /**
+     * Overwrite the fields of a row the file has tags for, keeping the rest.
+     */
+    pub fn apply_to(self, track: &mut TrackMetadata) {
+        for (field, value) in [
+            (&mut track.title, self.title),
+            (&mut track.artist, self.artist),
+            (&mut track.album, self.album),
+            (&mut track.genre, self.genre),
+            (&mut track.message, self.comment),
+        ] {
+            if !value.is_empty() {
+                *field = value;
+            }
+        }
+
+        if !self.bpm.is_empty() {
+            track.bpm = self.bpm.into();
+        }
+        if !self.key.is_empty() {
+            track.key = self.key.into();
+        }
+        if self.duration > 0. {
+            track.time = crate::track_metadata::format_time(self.duration).into();
+            track.media_segment.duration = self.duration;
+        }
+        track.media_segment.title = Some(format!("{} - {}", track.artist, track.title));
+    }
+}
+
+pub fn is_audio_file(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/**
+ * Probe a file and collect its tags.
+ * Tags found in the container win over ones read before it (e.g. ID3 on flac).
+ */
+pub fn read(path: &std::path::Path) -> Result<AudioTags, String> {
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let mut probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| e.to_string())?;
+
+    let mut tags = AudioTags::default();
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        apply_tags(&mut tags, revision.tags());
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        apply_tags(&mut tags, revision.tags());
+    }
+
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+        if let (Some(frames), Some(time_base)) = (params.n_frames, params.time_base) {
+            let time = time_base.calc_time(frames);
+            tags.duration = time.seconds as f32 + time.frac as f32;
+        } else if let (Some(frames), Some(rate)) = (params.n_frames, params.sample_rate) {
+            tags.duration = frames as f32 / rate as f32;
+        }
+    }
+
+    Ok(tags)
+}
+
+fn apply_tags(tags: &mut AudioTags, found: &[symphonia::core::meta::Tag]) {
+    use symphonia::core::meta::StandardTagKey;
+
+    for tag in found {
+        let value = tag.value.to_string().trim().to_string();
+        if value.is_empty() {
+            continue;
+        }
+
+        let field = match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => &mut tags.title,
+            Some(StandardTagKey::Artist) => &mut tags.artist,
+            Some(StandardTagKey::Album) => &mut tags.album,
+            Some(StandardTagKey::Bpm) => &mut tags.bpm,
+            Some(StandardTagKey::Genre) => &mut tags.genre,
+            Some(StandardTagKey::Comment) => &mut tags.comment,
+            // There is no standard key for the musical key, match the raw tag names instead.
+            _ => match tag.key.to_ascii_lowercase().as_str() {
+                "tkey" | "initialkey" | "key" | "----:com.apple.itunes:initialkey" => &mut tags.key,
+                _ => continue,
+            },
+        };
+
+        *field = value;
+    }
+}
@@ -0,0 +1,251 @@
+use crate::path_rules::{PathProfile, PathRule};
+use crate::track_metadata::TrackMetadata;
+
+enum BundleMessage {
+    Progress { done: usize, total: usize, file: String },
+    Finished(Result<BundleSummary, String>),
+}
+
+#[derive(Default)]
+pub struct BundleSummary {
+    pub copied: usize,
+    pub skipped: usize,
+    pub missing: Vec<String>,
+}
+
+/**
+*
+* Copy of every file in a set, numbered in set order, next to an m3u8
+* that references the copies relatively. Copying runs on a background
+* thread, poll it every frame.
+*
+*/
+pub struct Bundle {
+    pub dir: std::path::PathBuf,
+    pub done: usize,
+    pub total: usize,
+    pub file: String,
+    pub result: Option<Result<BundleSummary, String>>,
+    receiver: std::sync::mpsc::Receiver<BundleMessage>,
+    cancel: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl Bundle {
+    /**
+     * Ask for a folder and start copying the tracks into it.
+     */
+    pub fn pick(tracks: &[TrackMetadata]) -> Option<Self> {
+        rfd::FileDialog::new()
+            .set_directory("/")
+            .pick_folder()
+            .map(|dir| Self::start(dir, tracks.to_vec()))
+    }
+
+    pub fn start(dir: std::path::PathBuf, tracks: Vec<TrackMetadata>) -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let thread_dir = dir.clone();
+        let thread_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            let result = copy_tracks(&thread_dir, tracks, &thread_cancel, |done, total, file| {
+                let _ = sender.send(BundleMessage::Progress {
+                    done,
+                    total,
+                    file: file.to_string(),
+                });
+            });
+            let _ = sender.send(BundleMessage::Finished(result));
+        });
+
+        Self {
+            dir,
+            done: 0,
+            total: 0,
+            file: String::new(),
+            result: None,
+            receiver,
+            cancel,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /**
+     * Drain progress messages.
+     */
+    pub fn poll(&mut self) {
+        while let Ok(message) = self.receiver.try_recv() {
+            match message {
+                BundleMessage::Progress { done, total, file } => {
+                    self.done = done;
+                    self.total = total;
+                    self.file = file;
+                }
+                BundleMessage::Finished(result) => self.result = Some(result),
+            }
+        }
+    }
+
+    /**
+     * Draw copy progress, then the outcome. Returns false once the window is closed.
+     */
+    pub fn ui(&self, ctx: &eframe::egui::Context) -> bool {
+        use eframe::egui;
+        let mut keep = true;
+
+        egui::Window::new("Export bundle").collapsible(false).show(ctx, |ui| {
+            ui.label(self.dir.display().to_string());
+
+            match &self.result {
+                None => {
+                    let progress = if self.total > 0 { self.done as f32 / self.total as f32 } else { 0. };
+                    ui.add(egui::ProgressBar::new(progress).text(format!("{} / {}", self.done, self.total)));
+                    ui.label(&self.file);
+                    if ui.button("Cancel").clicked() {
+                        keep = false;
+                    }
+                }
+                Some(Ok(summary)) => {
+                    ui.label(format!("{} copied, {} already up to date", summary.copied, summary.skipped));
+                    for path in summary.missing.iter() {
+                        ui.colored_label(egui::Color32::YELLOW, format!("Missing: {}", path));
+                    }
+                    if ui.button("Close").clicked() {
+                        keep = false;
+                    }
+                }
+                Some(Err(error)) => {
+                    ui.colored_label(egui::Color32::RED, error);
+                    if ui.button("Close").clicked() {
+                        keep = false;
+                    }
+                }
+            }
+        });
+
+        crate::playlist::repaint_while_busy(ctx, self.result.is_none());
+        keep
+    }
+}
+
+fn copy_tracks(
+    dir: &std::path::Path,
+    tracks: Vec<TrackMetadata>,
+    cancel: &std::sync::atomic::AtomicBool,
+    progress: impl Fn(usize, usize, &str),
+) -> Result<BundleSummary, String> {
+    let mut summary = BundleSummary::default();
+    let width = tracks.len().to_string().len().max(2);
+
+    // Work out destinations first so free space can be checked before copying.
+    let mut copies = vec![];
+    for (i, track) in tracks.into_iter().enumerate() {
+        let source = std::path::PathBuf::from(&track.media_segment.uri);
+        let Ok(size) = std::fs::metadata(&source).map(|m| m.len()) else {
+            summary.missing.push(track.media_segment.uri.clone());
+            continue;
+        };
+        let destination = dir.join(bundle_file_name(i + 1, width, &track, &source));
+        copies.push((track, source, destination, size));
+    }
+
+    let needed: u64 = copies
+        .iter()
+        .filter(|(_, _, destination, size)| std::fs::metadata(destination).map_or(true, |m| m.len() != *size))
+        .map(|(_, _, _, size)| size)
+        .sum();
+    let available = fs2::available_space(dir).map_err(|e| format!("Could not check free space: {}", e))?;
+    if needed > available {
+        return Err(format!(
+            "Not enough free space: {} MB needed, {} MB available",
+            needed / 1_000_000,
+            available / 1_000_000
+        ));
+    }
+
+    let total = copies.len();
+    let mut bundled = vec![];
+    for (i, (mut track, source, destination, size)) in copies.into_iter().enumerate() {
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+
+        let name = destination.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        progress(i, total, &name);
+
+        if same_file(&source, &destination, size) {
+            summary.skipped += 1;
+        } else {
+            std::fs::copy(&source, &destination).map_err(|e| format!("Error copying {}: {}", name, e))?;
+            summary.copied += 1;
+        }
+
+        track.media_segment.uri = destination.to_string_lossy().into_owned();
+        bundled.push(track);
+    }
+    progress(total, total, "");
+
+    let playlist_path = dir.join(format!(
+        "{}.m3u8",
+        dir.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let relative = PathProfile {
+        name: String::new(),
+        rules: vec![PathRule::RelativeToPlaylist],
+    };
+    let name = dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let options = crate::m3u::WriteOptions {
+        name: &name,
+        profile: Some(&relative),
+        ..Default::default()
+    };
+    std::fs::File::create(&playlist_path)
+        .and_then(|mut f| crate::m3u::write_to(&bundled, &options, &playlist_path, &mut f))
+        .map_err(|e| format!("Error writing playlist: {}", e))?;
+
+    Ok(summary)
+}
+
+/**
+ * "01 - Artist - Title.mp3", without characters file systems reject.
+ */
+fn bundle_file_name(number: usize, width: usize, track: &TrackMetadata, source: &std::path::Path) -> String {
+    let name: String = crate::m3u::display_name(track)
+        .chars()
+        .map(|c| if "<>:\"/\\|?*".contains(c) || c.is_control() { '_' } else { c })
+        .collect();
+    let extension = source.extension().map_or(String::new(), |e| format!(".{}", e.to_string_lossy()));
+    format!("{:0width$} - {}{}", number, name.trim(), extension, width = width)
+}
+
+/**
+ * A copy is up to date when it has the same size and content hash.
+ */
+fn same_file(source: &std::path::Path, destination: &std::path::Path, size: u64) -> bool {
+    if std::fs::metadata(destination).map_or(true, |m| m.len() != size) {
+        return false;
+    }
+
+    match (content_hash(source), content_hash(destination)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// FNV-1a over the file contents.
+fn content_hash(path: &std::path::Path) -> std::io::Result<u64> {
+    use std::io::Read;
+    let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut hasher = crate::track_metadata::Fnv1a::default();
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::track_metadata::TrackMetadata;
+
+/**
+*
+* A DJ software library: every known track plus the playlist tree,
+* flattened to "Folder / Playlist" names.
+*
+*/
+pub struct Collection {
+    pub path: std::path::PathBuf,
+    pub tracks: std::collections::HashMap<String, TrackMetadata>,
+    pub playlists: Vec<CollectionPlaylist>,
+    selected: Option<usize>,
+}
+
+pub struct CollectionPlaylist {
+    pub name: String,
+    pub track_keys: Vec<String>,
+}
+
+pub enum CollectionAction {
+    None,
+    Load(usize),
+    Cancel,
+}
+
+impl Collection {
+    pub fn new(
+        path: std::path::PathBuf,
+        tracks: std::collections::HashMap<String, TrackMetadata>,
+        playlists: Vec<CollectionPlaylist>,
+    ) -> Self {
+        Self {
+            path,
+            tracks,
+            playlists,
+            selected: None,
+        }
+    }
+
+    /**
+     * Tracks of a playlist in playlist order, numbered by position.
+     * Entries missing from the collection are skipped.
+     */
+    pub fn playlist_tracks(&self, index: usize) -> Vec<TrackMetadata> {
+        self.playlists.get(index).map_or(vec![], |playlist| {
+            playlist
+                .track_keys
+                .iter()
+                .filter_map(|key| self.tracks.get(key).cloned())
+                .enumerate()
+                .map(|(i, mut track)| {
+                    track.number = i as u32 + 1;
+                    track
+                })
+                .collect()
+        })
+    }
+
+    pub fn ui(&mut self, ctx: &eframe::egui::Context) -> CollectionAction {
+        use eframe::egui;
+        let mut action = CollectionAction::None;
+        let mut open = true;
+
+        egui::Window::new("Choose playlist")
+            .open(&mut open)
+            .default_width(400.)
+            .show(ctx, |ui| {
+                ui.label(format!("{} ({} tracks)", self.path.display(), self.tracks.len()));
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(400.).show(ui, |ui| {
+                    for (i, playlist) in self.playlists.iter().enumerate() {
+                        let label = format!("{} ({})", playlist.name, playlist.track_keys.len());
+                        let response = ui.selectable_label(self.selected == Some(i), label);
+                        if response.clicked() {
+                            self.selected = Some(i);
+                        }
+                        if response.double_clicked() {
+                            action = CollectionAction::Load(i);
+                        }
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(self.selected.is_some(), egui::Button::new("Load")).clicked() {
+                        if let Some(i) = self.selected {
+                            action = CollectionAction::Load(i);
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        action = CollectionAction::Cancel;
+                    }
+                });
+            });
+
+        if !open {
+            action = CollectionAction::Cancel;
+        }
+
+        action
+    }
+}
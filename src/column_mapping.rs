@@ -0,0 +1,348 @@
+use crate::playlist::ImportError;
+use crate::track_metadata::TrackMetadata;
+
+// Column headers TrackMetadata deserializes from, as written by an English Rekordbox.
+// "Location" and "Duration" are our own additions so exported tables carry
+// their files and exact lengths.
+pub const FIELDS: [&str; 13] = [
+    "#", "Track Title", "Artist", "Album", "BPM", "Key", "Time", "Message", "Date Added", "Genre", "My Tag", "Location",
+    "Duration",
+];
+
+const PRESETS_CONFIG: &str = "column_presets";
+const PREVIEW_ROWS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ColumnRef {
+    Header(String),
+    Index(usize),
+}
+
+/**
+*
+* Maps every TrackMetadata field to a column of the imported file,
+* either by header name or by position.
+*
+*/
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ColumnMapping {
+    pub name: String,
+    pub columns: Vec<(String, Option<ColumnRef>)>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            name: "Rekordbox (English)".to_string(),
+            columns: FIELDS
+                .iter()
+                .map(|field| (field.to_string(), Some(ColumnRef::Header(field.to_string()))))
+                .collect(),
+        }
+    }
+}
+
+impl ColumnMapping {
+    pub fn column(&self, field: &str) -> Option<&ColumnRef> {
+        self.columns
+            .iter()
+            .find(|(f, _)| f == field)
+            .and_then(|(_, column)| column.as_ref())
+    }
+
+    pub fn set_column(&mut self, field: &str, column: Option<ColumnRef>) {
+        match self.columns.iter_mut().find(|(f, _)| f == field) {
+            Some(entry) => entry.1 = column,
+            None => self.columns.push((field.to_string(), column)),
+        }
+    }
+
+    /**
+     * Rewrite the file's header row so mapped columns carry the names
+     * TrackMetadata deserializes from. Unmapped fields are blanked out.
+     */
+    pub fn remap_headers(&self, headers: &csv::StringRecord) -> csv::StringRecord {
+        let mut remapped: Vec<String> = headers
+            .iter()
+            .map(|h| if FIELDS.contains(&h) { String::new() } else { h.to_string() })
+            .collect();
+
+        for field in FIELDS {
+            let index = match self.column(field) {
+                Some(ColumnRef::Header(name)) => headers.iter().position(|h| h == name),
+                Some(ColumnRef::Index(i)) => Some(*i),
+                None => None,
+            };
+
+            if let Some(index) = index {
+                if index >= remapped.len() {
+                    remapped.resize(index + 1, String::new());
+                }
+                remapped[index] = field.to_string();
+            }
+        }
+
+        csv::StringRecord::from(remapped)
+    }
+}
+
+/**
+ * Built-in mapping followed by the user's saved presets.
+ */
+pub fn load_presets() -> Vec<ColumnMapping> {
+    let mut presets = vec![ColumnMapping::default()];
+    presets.extend(crate::config::load::<Vec<ColumnMapping>>(PRESETS_CONFIG));
+    presets
+}
+
+fn save_presets(presets: &[ColumnMapping]) -> std::io::Result<()> {
+    let default_name = ColumnMapping::default().name;
+    let user_presets: Vec<&ColumnMapping> = presets.iter().filter(|p| p.name != default_name).collect();
+    crate::config::save(PRESETS_CONFIG, &user_presets)
+}
+
+pub enum PreviewAction {
+    None,
+    Import,
+    Cancel,
+}
+
+/**
+*
+* A decoded csv file waiting to be imported,
+* along with the mapping the user is editing for it.
+*
+*/
+pub struct ImportPreview {
+    pub path: std::path::PathBuf,
+    text: String,
+    delimiter: u8,
+    headers: csv::StringRecord,
+    pub mapping: ColumnMapping,
+    presets: Vec<ColumnMapping>,
+    preset_name: String,
+}
+
+impl ImportPreview {
+    /**
+     * Ask for a csv file and open a preview of it.
+     */
+    pub fn pick() -> Option<Result<Self, ImportError>> {
+        rfd::FileDialog::new()
+            .add_filter("csv", &["txt", "csv", "tsv"])
+            .set_directory("/")
+            .pick_file()
+            .map(Self::open)
+    }
+
+    pub fn open(path: std::path::PathBuf) -> Result<Self, ImportError> {
+        let text = crate::text_export::read_to_string(&path)?;
+        let delimiter = crate::text_export::detect_delimiter(&text);
+        let headers = Self::reader_for(&text, delimiter).headers()?.clone();
+        let presets = load_presets();
+
+        // Prefer the first preset that finds all of its named columns in this file.
+        let mapping = presets
+            .iter()
+            .find(|preset| {
+                preset.columns.iter().all(|(_, column)| match column {
+                    Some(ColumnRef::Header(name)) => headers.iter().any(|h| h == name),
+                    _ => true,
+                })
+            })
+            .unwrap_or(&presets[0])
+            .clone();
+
+        Ok(Self {
+            path,
+            text,
+            delimiter,
+            headers,
+            preset_name: mapping.name.clone(),
+            mapping,
+            presets,
+        })
+    }
+
+    fn reader_for(text: &str, delimiter: u8) -> csv::Reader<&[u8]> {
+        csv::ReaderBuilder::default()
+            .has_headers(true)
+            .flexible(true)
+            .trim(csv::Trim::Headers)
+            .delimiter(delimiter)
+            .from_reader(text.as_bytes())
+    }
+
+    /**
+     * Parse rows with the current mapping.
+     * Rows that fail are returned as warnings with their line number.
+     */
+    pub fn tracks(&self, limit: Option<usize>) -> (Vec<TrackMetadata>, Vec<ImportError>) {
+        let headers = self.mapping.remap_headers(&self.headers);
+        let mut reader = Self::reader_for(&self.text, self.delimiter);
+        let mut tracks = vec![];
+        let mut warnings = vec![];
+        // Without a "#" column rows are numbered by their position in the file.
+        let numbered = headers.iter().any(|h| h == "#");
+
+        for (position, result) in reader.records().take(limit.unwrap_or(usize::MAX)).enumerate() {
+            let parsed = result.and_then(|record| record.deserialize::<TrackMetadata>(Some(&headers)));
+
+            match parsed {
+                Ok(mut track) => {
+                    if !numbered {
+                        track.number = position as u32 + 1;
+                    }
+                    if !track.media_segment.uri.is_empty() {
+                        track.media_segment.duration = track.duration.value.or(track.duration_secs()).unwrap_or(0.);
+                        track.media_segment.title = Some(format!("{} - {}", track.artist, track.title));
+                    }
+                    track.update_id();
+                    tracks.push(track);
+                }
+                Err(error) => warnings.push(ImportError::CsvRow {
+                    line: error.position().map_or(0, |p| p.line()),
+                    error,
+                }),
+            }
+        }
+
+        (tracks, warnings)
+    }
+
+    fn column_label(&self, column: &Option<ColumnRef>) -> String {
+        match column {
+            Some(ColumnRef::Header(name)) => format!("\"{}\"", name),
+            Some(ColumnRef::Index(i)) => format!("Column {}", i + 1),
+            None => "(not mapped)".to_string(),
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &eframe::egui::Context) -> PreviewAction {
+        use eframe::egui;
+        let mut action = PreviewAction::None;
+        let mut open = true;
+
+        egui::Window::new("Import preview")
+            .open(&mut open)
+            .default_width(700.)
+            .show(ctx, |ui| {
+                ui.label(self.path.display().to_string());
+
+                ui.horizontal(|ui| {
+                    ui.label("Preset:");
+                    let mut selected = None;
+                    egui::ComboBox::from_id_salt("column preset")
+                        .selected_text(self.mapping.name.as_str())
+                        .show_ui(ui, |ui| {
+                            for (i, preset) in self.presets.iter().enumerate() {
+                                if ui.selectable_label(preset.name == self.mapping.name, preset.name.as_str()).clicked() {
+                                    selected = Some(i);
+                                }
+                            }
+                        });
+                    if let Some(i) = selected {
+                        self.mapping = self.presets[i].clone();
+                        self.preset_name = self.mapping.name.clone();
+                    }
+
+                    ui.text_edit_singleline(&mut self.preset_name);
+                    if ui.button("Save preset").clicked() && !self.preset_name.trim().is_empty() {
+                        self.mapping.name = self.preset_name.trim().to_string();
+                        match self.presets.iter_mut().find(|p| p.name == self.mapping.name) {
+                            Some(preset) => *preset = self.mapping.clone(),
+                            None => self.presets.push(self.mapping.clone()),
+                        }
+                        if let Err(e) = save_presets(&self.presets) {
+                            crate::config::report(format!("Could not save presets: {}", e));
+                        }
+                    }
+                });
+
+                ui.separator();
+                egui::Grid::new("column mapping").num_columns(4).show(ui, |ui| {
+                    for (n, field) in FIELDS.iter().enumerate() {
+                        let mut column = self.mapping.column(field).cloned();
+                        ui.label(*field);
+                        egui::ComboBox::from_id_salt(("mapping", *field))
+                            .width(150.)
+                            .selected_text(self.column_label(&column))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut column, None, self.column_label(&None));
+                                for header in self.headers.iter().filter(|h| !h.is_empty()) {
+                                    let value = Some(ColumnRef::Header(header.to_string()));
+                                    let label = self.column_label(&value);
+                                    ui.selectable_value(&mut column, value, label);
+                                }
+                                for i in 0..self.headers.len() {
+                                    let value = Some(ColumnRef::Index(i));
+                                    let label = self.column_label(&value);
+                                    ui.selectable_value(&mut column, value, label);
+                                }
+                            });
+                        self.mapping.set_column(field, column);
+
+                        if n % 2 == 1 {
+                            ui.end_row();
+                        }
+                    }
+                });
+
+                ui.separator();
+                let (tracks, warnings) = self.tracks(Some(PREVIEW_ROWS));
+                egui::ScrollArea::both().max_height(250.).show(ui, |ui| {
+                    egui::Grid::new("import preview rows").striped(true).show(ui, |ui| {
+                        for field in FIELDS {
+                            ui.strong(field);
+                        }
+                        ui.end_row();
+
+                        for track in tracks.iter() {
+                            for (value, invalid) in [
+                                (track.number.to_string(), false),
+                                (track.title.clone(), false),
+                                (track.artist.clone(), false),
+                                (track.album.clone(), false),
+                                (track.bpm.to_string(), track.bpm.is_invalid()),
+                                (track.key.to_string(), track.key.is_invalid()),
+                                (track.time.to_string(), track.time.is_invalid()),
+                                (track.message.clone(), false),
+                                (track.date_added.to_string(), track.date_added.is_invalid()),
+                                (track.genre.clone(), false),
+                                (track.my_tag.clone(), false),
+                                (track.media_segment.uri.clone(), false),
+                                (track.duration.to_string(), track.duration.is_invalid()),
+                            ] {
+                                if invalid {
+                                    crate::playlist::show_invalid(ui, &value);
+                                } else {
+                                    ui.label(value);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+
+                    for warning in warnings.iter() {
+                        ui.colored_label(egui::Color32::from_rgb(220, 180, 40), warning.to_string());
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Import").clicked() {
+                        action = PreviewAction::Import;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        action = PreviewAction::Cancel;
+                    }
+                });
+            });
+
+        if !open {
+            action = PreviewAction::Cancel;
+        }
+
+        action
+    }
+}
@@ -0,0 +1,64 @@
+/*!
+*
+* Small json store for user settings and presets,
+* kept in the platform config directory.
+*
+*/
+
+// Load and save failures waiting to be shown in the problems panel.
+static PROBLEMS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+/**
+ * Queue a config problem for the problems panel.
+ */
+pub fn report(problem: String) {
+    if let Ok(mut problems) = PROBLEMS.lock() {
+        problems.push(problem);
+    }
+}
+
+/**
+ * Drain the problems reported since the last call.
+ */
+pub fn take_problems() -> Vec<String> {
+    PROBLEMS.lock().map(|mut problems| std::mem::take(&mut *problems)).unwrap_or_default()
+}
+
+fn path(name: &str) -> Option<std::path::PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("playlists").join(format!("{}.json", name)))
+}
+
+/**
+ * Load a config file, falling back to the default when missing or unreadable.
+ */
+pub fn load<T: serde::de::DeserializeOwned + Default>(name: &str) -> T {
+    path(name)
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|bytes| match serde_json::from_slice(&bytes) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                report(format!("Could not read config {}: {}", name, e));
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+/**
+ * Whether the config file was written before, even if it no longer parses.
+ */
+pub fn exists(name: &str) -> bool {
+    path(name).is_some_and(|path| path.exists())
+}
+
+pub fn save<T: serde::Serialize>(name: &str, value: &T) -> std::io::Result<()> {
+    let Some(path) = path(name) else {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No config directory"));
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    std::fs::write(path, serde_json::to_vec_pretty(value)?)
+}
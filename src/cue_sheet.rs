@@ -0,0 +1,305 @@
+/*!
+*
+* CUE sheet and timestamped tracklist for a recording of the exported set.
+*
+*/
+use crate::track_metadata::{TrackMetadata, TrackTime};
+
+/**
+ * Row of the transition dialog. Start and overlap are kept as text while editing.
+ * Starts typed in by hand are kept when recalculating.
+ */
+struct Transition {
+    start: String,
+    start_edited: bool,
+    overlap: String,
+}
+
+/**
+*
+* Dialog to adjust where each track starts in the recording before
+* writing the CUE sheet or tracklist.
+*
+*/
+pub struct MixTimeline {
+    tracks: Vec<TrackMetadata>,
+    transitions: Vec<Transition>,
+    default_overlap: String,
+    recording: String,
+    last_error: Option<String>,
+}
+
+impl MixTimeline {
+    pub fn new(tracks: &[TrackMetadata]) -> Self {
+        let mut timeline = Self {
+            tracks: tracks.to_vec(),
+            transitions: tracks
+                .iter()
+                .map(|_| Transition {
+                    start: String::new(),
+                    start_edited: false,
+                    overlap: String::new(),
+                })
+                .collect(),
+            default_overlap: "0".to_string(),
+            recording: "mix.wav".to_string(),
+            last_error: None,
+        };
+        timeline.recalculate();
+        timeline
+    }
+
+    /**
+     * Start times from the track durations, minus the overlap of each transition.
+     * Hand edited starts stay and the tracks after them follow on from there.
+     */
+    fn recalculate(&mut self) {
+        let default_overlap = parse_secs(&self.default_overlap).unwrap_or(0.);
+        let mut start = 0.;
+
+        for (track, transition) in self.tracks.iter().zip(self.transitions.iter_mut()) {
+            match parse_secs(&transition.start) {
+                Some(edited) if transition.start_edited => start = edited,
+                _ => transition.start = format_timestamp(start),
+            }
+            let overlap = parse_secs(&transition.overlap).unwrap_or(default_overlap);
+            start = (start + track.duration_secs().unwrap_or(track.media_segment.duration) - overlap).max(start);
+        }
+    }
+
+    /**
+     * Parsed start times, which must not go back in time from one track to the next.
+     */
+    fn starts(&self) -> Result<Vec<f32>, String> {
+        let starts = self
+            .transitions
+            .iter()
+            .enumerate()
+            .map(|(i, t)| parse_secs(&t.start).ok_or(format!("Invalid start time for track {}: {}", i + 1, t.start)))
+            .collect::<Result<Vec<f32>, String>>()?;
+        check_order(&starts)?;
+        Ok(starts)
+    }
+
+    /**
+     * Draw the dialog. Returns false once it is closed.
+     */
+    pub fn ui(&mut self, ctx: &eframe::egui::Context) -> bool {
+        use eframe::egui;
+        let mut open = true;
+        let mut keep = true;
+        let mut recalculate = false;
+
+        egui::Window::new("Mix timestamps")
+            .open(&mut open)
+            .default_width(500.)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Recording file:");
+                    ui.text_edit_singleline(&mut self.recording);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Default overlap (s):");
+                    recalculate |= ui.add(egui::TextEdit::singleline(&mut self.default_overlap).desired_width(50.)).lost_focus();
+                    recalculate |= ui.button("Recalculate").clicked();
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(400.).show(ui, |ui| {
+                    egui::Grid::new("mix transitions").striped(true).show(ui, |ui| {
+                        ui.strong("#");
+                        ui.strong("Track");
+                        ui.strong("Start");
+                        ui.strong("Overlap (s)");
+                        ui.end_row();
+
+                        for (i, (track, transition)) in self.tracks.iter().zip(self.transitions.iter_mut()).enumerate() {
+                            ui.label((i + 1).to_string());
+                            ui.label(format!("{} - {}", track.artist, track.title));
+                            let valid = parse_secs(&transition.start).is_some();
+                            ui.horizontal(|ui| {
+                                let response = ui.add(
+                                    egui::TextEdit::singleline(&mut transition.start)
+                                        .desired_width(70.)
+                                        .text_color_opt((!valid).then_some(egui::Color32::from_rgb(230, 140, 30))),
+                                );
+                                transition.start_edited |= response.changed();
+                                recalculate |= response.lost_focus();
+
+                                if transition.start_edited
+                                    && ui.small_button("↺").on_hover_text("Calculate this start again").clicked()
+                                {
+                                    transition.start_edited = false;
+                                    recalculate = true;
+                                }
+                            });
+                            recalculate |= ui
+                                .add(
+                                    egui::TextEdit::singleline(&mut transition.overlap)
+                                        .desired_width(50.)
+                                        .hint_text(&self.default_overlap),
+                                )
+                                .lost_focus();
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.separator();
+                if let Some(error) = &self.last_error {
+                    ui.colored_label(egui::Color32::from_rgb(220, 60, 60), error);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Write CUE").clicked() {
+                        self.last_error = self.export(Format::Cue).err();
+                    }
+                    if ui.button("Write tracklist").clicked() {
+                        self.last_error = self.export(Format::Tracklist).err();
+                    }
+                    if ui.button("Close").clicked() {
+                        keep = false;
+                    }
+                });
+            });
+
+        if recalculate {
+            self.recalculate();
+        }
+
+        keep && open
+    }
+
+    fn export(&self, format: Format) -> Result<(), String> {
+        let starts = self.starts()?;
+
+        let dialog = match format {
+            Format::Cue => rfd::FileDialog::new().add_filter("CUE sheet", &["cue"]),
+            Format::Tracklist => rfd::FileDialog::new().add_filter("Text", &["txt"]),
+        };
+
+        let Some(file) = dialog.set_directory("/").save_file() else {
+            return Ok(());
+        };
+
+        let title = file.file_stem().map_or("Mix".to_string(), |n| n.to_string_lossy().into_owned());
+        let f = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(file);
+
+        f.and_then(|mut f| match format {
+            Format::Cue => write_cue(&self.tracks, &starts, &title, &self.recording, &mut f),
+            Format::Tracklist => write_tracklist(&self.tracks, &starts, &mut f),
+        })
+        .map_err(|e| format!("Could not write file: {}", e))
+    }
+}
+
+enum Format {
+    Cue,
+    Tracklist,
+}
+
+fn check_order(starts: &[f32]) -> Result<(), String> {
+    match starts.windows(2).position(|pair| pair[1] < pair[0]) {
+        Some(i) => Err(format!("Track {} starts before track {}.", i + 2, i + 1)),
+        None => Ok(()),
+    }
+}
+
+fn parse_secs(text: &str) -> Option<f32> {
+    text.trim().parse::<TrackTime>().ok().map(|t| t.0.as_secs_f32())
+}
+
+/**
+ * "mm:ss", or "h:mm:ss" once the mix passes an hour.
+ */
+fn format_timestamp(secs: f32) -> String {
+    let secs = secs.max(0.).round() as u32;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{:02}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn quote(text: &str) -> String {
+    text.replace('"', "'")
+}
+
+/**
+ * CUE sheet with one TRACK per row. INDEX is mm:ss:ff with 75 frames a second.
+ */
+pub fn write_cue<T: std::io::Write>(
+    tracks: &[TrackMetadata],
+    starts: &[f32],
+    title: &str,
+    recording: &str,
+    w: &mut T,
+) -> std::io::Result<()> {
+    let file_type = if recording.to_ascii_lowercase().ends_with(".mp3") { "MP3" } else { "WAVE" };
+
+    writeln!(w, "TITLE \"{}\"", quote(title))?;
+    writeln!(w, "FILE \"{}\" {}", quote(recording), file_type)?;
+
+    for (i, (track, start)) in tracks.iter().zip(starts).enumerate() {
+        let frames = (start.max(0.) * 75.).round() as u32;
+        writeln!(w, "  TRACK {:02} AUDIO", i + 1)?;
+        writeln!(w, "    TITLE \"{}\"", quote(&track.title))?;
+        writeln!(w, "    PERFORMER \"{}\"", quote(&track.artist))?;
+        writeln!(w, "    INDEX 01 {:02}:{:02}:{:02}", frames / 75 / 60, frames / 75 % 60, frames % 75)?;
+    }
+
+    Ok(())
+}
+
+/**
+ * "00:00 Artist - Title" lines, for mix descriptions.
+ */
+pub fn write_tracklist<T: std::io::Write>(tracks: &[TrackMetadata], starts: &[f32], w: &mut T) -> std::io::Result<()> {
+    for (track, start) in tracks.iter().zip(starts) {
+        if track.artist.is_empty() {
+            writeln!(w, "{} {}", format_timestamp(*start), track.title)?;
+        } else {
+            writeln!(w, "{} {} - {}", format_timestamp(*start), track.artist, track.title)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_switch_to_hours() {
+        assert_eq!(format_timestamp(0.), "00:00");
+        assert_eq!(format_timestamp(599.6), "10:00");
+        assert_eq!(format_timestamp(3725.), "1:02:05");
+    }
+
+    #[test]
+    fn starts_must_not_go_back() {
+        assert!(check_order(&[0., 300., 300., 620.]).is_ok());
+        assert_eq!(check_order(&[0., 300., 200.]), Err("Track 3 starts before track 2.".to_string()));
+    }
+
+    #[test]
+    fn cue_index_uses_75_frames() {
+        let track = TrackMetadata {
+            title: "Title \"quoted\"".to_string(),
+            artist: "Artist".to_string(),
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write_cue(&[track.clone(), track], &[0., 3723.4], "Mix", "mix.mp3", &mut out).unwrap();
+        let cue = String::from_utf8(out).unwrap();
+
+        assert!(cue.starts_with("TITLE \"Mix\"\nFILE \"mix.mp3\" MP3\n"));
+        assert!(cue.contains("  TRACK 01 AUDIO\n    TITLE \"Title 'quoted'\"\n    PERFORMER \"Artist\"\n    INDEX 01 00:00:00\n"));
+        assert!(cue.contains("  TRACK 02 AUDIO"));
+        assert!(cue.contains("    INDEX 01 62:03:30\n"));
+    }
+}
@@ -0,0 +1,47 @@
+/*!
+*
+* Playlists Engine DJ (Denon, Numark) can import. Engine keeps its library
+* in a database, so we write the plain m3u its import accepts.
+*
+*/
+use crate::path_rules::PathProfile;
+use crate::track_metadata::TrackMetadata;
+
+/**
+ * Ask for a file name and write the tracks as an Engine DJ playlist.
+ */
+pub fn export(tracks: &[TrackMetadata], profile: Option<&PathProfile>) -> std::io::Result<()> {
+    let file = rfd::FileDialog::new()
+        .add_filter("Engine DJ playlist", &["m3u"])
+        .set_directory("/")
+        .save_file();
+
+    let Some(file) = file else {
+        return Ok(());
+    };
+
+    let mut f = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&file)?;
+    write_to(tracks, profile, &file, &mut f)
+}
+
+/**
+ * Write only `#EXTINF` and the path of each track, Engine skips anything
+ * else. Paths always use forward slashes, also for windows drives.
+ */
+pub fn write_to<T: std::io::Write>(
+    tracks: &[TrackMetadata],
+    profile: Option<&PathProfile>,
+    playlist_path: &std::path::Path,
+    w: &mut T,
+) -> std::io::Result<()> {
+    let options = crate::m3u::WriteOptions {
+        profile,
+        plain: true,
+        ..Default::default()
+    };
+    crate::m3u::write_to(tracks, &options, playlist_path, w)
+}
@@ -0,0 +1,195 @@
+use crate::playlist::ImportError;
+use crate::track_metadata::TrackMetadata;
+
+enum ScanMessage {
+    Progress { done: usize, total: usize },
+    Finished(Vec<TrackMetadata>, Vec<ImportError>),
+}
+
+/**
+*
+* Reads the tags of a music folder (recursively) or of the files a
+* playlist lists, on a background thread.
+* Poll it every frame until it hands back the tracks.
+*
+*/
+pub struct FolderScan {
+    pub root: std::path::PathBuf,
+    pub done: usize,
+    pub total: usize,
+    receiver: std::sync::mpsc::Receiver<ScanMessage>,
+    cancel: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl FolderScan {
+    /**
+     * Ask for a folder and start scanning it.
+     */
+    pub fn pick() -> Option<Self> {
+        rfd::FileDialog::new()
+            .set_directory("/")
+            .pick_folder()
+            .map(Self::start)
+    }
+
+    pub fn start(root: std::path::PathBuf) -> Self {
+        let thread_root = root.clone();
+        Self::spawn(root, move || {
+            let mut files = vec![];
+            collect_audio_files(&thread_root, &mut files);
+            files.sort();
+
+            files
+                .into_iter()
+                .map(|path| {
+                    let track = TrackMetadata {
+                        title: path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned()),
+                        media_segment: m3u8_rs::MediaSegment {
+                            uri: path.to_string_lossy().into_owned(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    };
+                    (track, false)
+                })
+                .collect()
+        })
+    }
+
+    /**
+     * Ask for a M3U, m3u8, XSPF or PLS playlist and read the tags of its files.
+     */
+    pub fn pick_playlist() -> Option<Result<Self, ImportError>> {
+        rfd::FileDialog::new()
+            .add_filter("Playlists", &crate::playlist::PLAYLIST_EXTENSIONS)
+            .add_filter("m3u8", &["m3u8", "m3u"])
+            .add_filter("XSPF", &["xspf"])
+            .add_filter("PLS", &["pls"])
+            .set_directory("/")
+            .pick_file()
+            .map(|path| {
+                let text = crate::text_export::read_to_string(&path)?;
+                let extension = path.extension().map_or(String::new(), |e| e.to_string_lossy().to_ascii_lowercase());
+                let entries = match extension.as_str() {
+                    "xspf" => crate::xspf::parse(&path, &text)?,
+                    "pls" => crate::pls::parse(&path, &text)?,
+                    _ => crate::m3u::parse(&path, &text),
+                };
+                // What the playlist says is kept when the file has no tags or is missing.
+                Ok(Self::spawn(path, move || entries.into_iter().map(|t| (t, true)).collect()))
+            })
+    }
+
+    /**
+     * Read tags on a background thread for the rows `entries` lists, each
+     * with a flag to keep the row when its file can't be read.
+     */
+    fn spawn(
+        root: std::path::PathBuf,
+        entries: impl FnOnce() -> Vec<(TrackMetadata, bool)> + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let thread_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            let entries = entries();
+            let total = entries.len();
+            let mut tracks = vec![];
+            let mut warnings = vec![];
+
+            for (i, (mut track, keep_untagged)) in entries.into_iter().enumerate() {
+                if thread_cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                    return;
+                }
+
+                let path = std::path::PathBuf::from(&track.media_segment.uri);
+                match crate::audio_tags::read(&path) {
+                    Ok(tags) => tags.apply_to(&mut track),
+                    Err(error) => {
+                        warnings.push(ImportError::Tags {
+                            path: track.media_segment.uri.clone(),
+                            error,
+                        });
+                        if !keep_untagged {
+                            let _ = sender.send(ScanMessage::Progress { done: i + 1, total });
+                            continue;
+                        }
+                    }
+                }
+
+                track.number = tracks.len() as u32 + 1;
+                track.update_id();
+                tracks.push(track);
+
+                let _ = sender.send(ScanMessage::Progress { done: i + 1, total });
+            }
+
+            let _ = sender.send(ScanMessage::Finished(tracks, warnings));
+        });
+
+        Self {
+            root,
+            done: 0,
+            total: 0,
+            receiver,
+            cancel,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /**
+     * Drain progress messages, returning the result once the scan is finished.
+     */
+    pub fn poll(&mut self) -> Option<(Vec<TrackMetadata>, Vec<ImportError>)> {
+        while let Ok(message) = self.receiver.try_recv() {
+            match message {
+                ScanMessage::Progress { done, total } => {
+                    self.done = done;
+                    self.total = total;
+                }
+                ScanMessage::Finished(tracks, warnings) => return Some((tracks, warnings)),
+            }
+        }
+
+        None
+    }
+
+    /**
+     * Draw scan progress. Returns false when the user cancels.
+     */
+    pub fn ui(&self, ctx: &eframe::egui::Context) -> bool {
+        use eframe::egui;
+        let mut keep = true;
+
+        egui::Window::new("Reading tags").collapsible(false).show(ctx, |ui| {
+            ui.label(self.root.display().to_string());
+            let progress = if self.total > 0 { self.done as f32 / self.total as f32 } else { 0. };
+            ui.add(egui::ProgressBar::new(progress).text(format!("{} / {}", self.done, self.total)));
+            if ui.button("Cancel").clicked() {
+                keep = false;
+            }
+        });
+
+        crate::playlist::repaint_while_busy(ctx, true);
+        keep
+    }
+}
+
+fn collect_audio_files(dir: &std::path::Path, files: &mut Vec<std::path::PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_audio_files(&path, files);
+        } else if crate::audio_tags::is_audio_file(&path) {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::track_metadata::{color_from_key_compare, CamelotKey, KeyCompareResult};
+
+const RULES_CONFIG: &str = "harmonic_rules";
+
+/**
+ * How the mode may change between the two keys.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum ModeChange {
+    Same,
+    Switch,
+    ToMajor,
+    ToMinor,
+    Any,
+}
+
+/**
+ * Distance from the current key to the next one.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum KeyMove {
+    // Steps clockwise on the camelot wheel, negative for counter-clockwise.
+    Steps(i8),
+    // Semitones up, negative for down.
+    Semitones(i8),
+}
+
+/**
+*
+* One transition, e.g. "Energy boost +2" is two steps clockwise
+* in the same mode. `result` rates it, `color` overrides the color
+* that goes with the result.
+*
+*/
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct KeyRule {
+    pub name: String,
+    pub result: KeyCompareResult,
+    #[serde(default)]
+    pub color: Option<[u8; 3]>,
+    pub movement: KeyMove,
+    pub mode: ModeChange,
+    // Also match the same move in the other direction.
+    #[serde(default)]
+    pub either_direction: bool,
+}
+
+impl KeyRule {
+    fn new(name: &str, result: KeyCompareResult, movement: KeyMove, mode: ModeChange, either_direction: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            result,
+            color: None,
+            movement,
+            mode,
+            either_direction,
+        }
+    }
+
+    fn with_color(mut self, color: [u8; 3]) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /**
+     * Fill for keys this rule matches. Incompatible transitions are never filled.
+     */
+    pub fn color(&self) -> Option<eframe::egui::Color32> {
+        match (self.result, self.color) {
+            (KeyCompareResult::Incompatible, _) => None,
+            (_, Some([r, g, b])) => Some(eframe::egui::Color32::from_rgb(r, g, b)),
+            (result, None) => color_from_key_compare(result),
+        }
+    }
+
+    /**
+     * Whether going from `from` to `to` is this move.
+     */
+    pub fn matches(&self, from: &CamelotKey, to: &CamelotKey) -> bool {
+        let mode = match self.mode {
+            ModeChange::Same => from.minor == to.minor,
+            ModeChange::Switch => from.minor != to.minor,
+            ModeChange::ToMajor => from.minor && !to.minor,
+            ModeChange::ToMinor => !from.minor && to.minor,
+            ModeChange::Any => true,
+        };
+
+        let moved = match self.movement {
+            KeyMove::Steps(steps) if self.either_direction => {
+                let steps = (steps as i32).rem_euclid(12) as u8;
+                from.distance(to) == steps.min(12 - steps)
+            }
+            KeyMove::Steps(steps) => (to.number as i32 - from.number as i32 - steps as i32).rem_euclid(12) == 0,
+            KeyMove::Semitones(semitones) => {
+                let delta = to.pitch() as i32 - from.pitch() as i32;
+                (delta - semitones as i32).rem_euclid(12) == 0
+                    || self.either_direction && (delta + semitones as i32).rem_euclid(12) == 0
+            }
+        };
+
+        mode && moved
+    }
+}
+
+/**
+*
+* Named list of rules, the first one that matches a transition wins.
+*
+*/
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RuleSet {
+    pub name: String,
+    pub rules: Vec<KeyRule>,
+}
+
+impl RuleSet {
+    pub fn matching(&self, from: &CamelotKey, to: &CamelotKey) -> Option<&KeyRule> {
+        self.rules.iter().find(|rule| rule.matches(from, to))
+    }
+}
+
+/**
+ * Rule sets that ship with the app.
+ */
+pub fn presets() -> Vec<RuleSet> {
+    use KeyCompareResult::*;
+    use KeyMove::*;
+    use ModeChange::*;
+
+    let same_key = || KeyRule::new("Same key", PerfectMatch, Steps(0), Same, false);
+    let relative = || KeyRule::new("Relative major/minor", Compatible, Steps(0), Switch, false);
+    let adjacent = || KeyRule::new("Adjacent", Compatible, Steps(1), Same, true);
+
+    vec![
+        RuleSet {
+            name: "Camelot".to_string(),
+            rules: vec![
+                same_key(),
+                relative(),
+                adjacent(),
+                KeyRule::new("Two steps", Compatible, Steps(2), Same, true),
+                KeyRule::new("Adjacent, mode change", MaybeCompatible, Steps(1), Switch, true),
+                KeyRule::new("Two steps, mode change", MaybeCompatible, Steps(2), Switch, true),
+                KeyRule::new("Three steps", MaybeCompatible, Steps(3), Same, true),
+                KeyRule::new("Five steps", MaybeCompatible, Steps(5), Same, true),
+            ],
+        },
+        RuleSet {
+            name: "Energy boost".to_string(),
+            rules: vec![
+                same_key(),
+                relative(),
+                adjacent(),
+                KeyRule::new("Energy boost +2", Compatible, Steps(2), Same, false).with_color([180, 90, 20]),
+                // +7 on the wheel is a semitone up.
+                KeyRule::new("Energy boost +7", MaybeCompatible, Steps(7), Same, false).with_color([150, 40, 140]),
+            ],
+        },
+        RuleSet {
+            name: "Relative only".to_string(),
+            rules: vec![same_key(), relative()],
+        },
+        RuleSet {
+            name: "Mood change".to_string(),
+            rules: vec![
+                same_key(),
+                relative(),
+                adjacent(),
+                KeyRule::new("Diagonal to major", Compatible, Steps(1), ToMajor, false).with_color([40, 120, 130]),
+                KeyRule::new("Diagonal to minor", Compatible, Steps(-1), ToMinor, false).with_color([40, 120, 130]),
+            ],
+        },
+    ]
+}
+
+/**
+ * Presets plus the rule sets in the config file, which replace presets of
+ * the same name. Without a config file the presets are written as a starting
+ * point, a file that doesn't parse is left alone for the user to fix.
+ */
+pub fn load_rule_sets() -> Vec<RuleSet> {
+    let mut sets = presets();
+    if !crate::config::exists(RULES_CONFIG) {
+        if let Err(e) = crate::config::save(RULES_CONFIG, &sets) {
+            crate::config::report(format!("Could not save harmonic rules: {}", e));
+        }
+    }
+
+    let user_sets = crate::config::load::<Vec<RuleSet>>(RULES_CONFIG);
+
+    for set in user_sets {
+        match sets.iter_mut().find(|s| s.name == set.name) {
+            Some(existing) => *existing = set,
+            None => sets.push(set),
+        }
+    }
+
+    sets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> CamelotKey {
+        s.parse().unwrap()
+    }
+
+    fn matching<'a>(set: &'a RuleSet, from: &str, to: &str) -> Option<&'a str> {
+        set.matching(&key(from), &key(to)).map(|rule| rule.name.as_str())
+    }
+
+    #[test]
+    fn camelot_rules_go_both_ways() {
+        let camelot = &presets()[0];
+        assert_eq!(matching(camelot, "8A", "8A"), Some("Same key"));
+        assert_eq!(matching(camelot, "8A", "8B"), Some("Relative major/minor"));
+        assert_eq!(matching(camelot, "12A", "1A"), Some("Adjacent"));
+        assert_eq!(matching(camelot, "1A", "12A"), Some("Adjacent"));
+        assert_eq!(matching(camelot, "3A", "10A"), Some("Five steps"));
+        assert_eq!(matching(camelot, "8A", "2A"), None);
+    }
+
+    #[test]
+    fn energy_boost_goes_one_way() {
+        let boost = &presets()[1];
+        assert_eq!(matching(boost, "8A", "10A"), Some("Energy boost +2"));
+        assert_eq!(matching(boost, "10A", "8A"), None);
+        assert_eq!(matching(boost, "8A", "3A"), Some("Energy boost +7"));
+    }
+
+    #[test]
+    fn colors_follow_the_result() {
+        use KeyCompareResult::*;
+        let rule = |result| KeyRule::new("Rule", result, KeyMove::Steps(0), ModeChange::Same, false);
+
+        assert_eq!(rule(Compatible).color(), color_from_key_compare(Compatible));
+        assert_eq!(
+            rule(Compatible).with_color([1, 2, 3]).color(),
+            Some(eframe::egui::Color32::from_rgb(1, 2, 3))
+        );
+        assert_eq!(rule(Incompatible).color(), None);
+        assert_eq!(rule(Incompatible).with_color([1, 2, 3]).color(), None);
+    }
+}
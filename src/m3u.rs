@@ -0,0 +1,225 @@
+/*!
+*
+* Extended M3U, as read by VLC, foobar2000 and most DJ software.
+* Reading also accepts plain M3U with nothing but paths.
+*
+*/
+use crate::path_rules::PathProfile;
+use crate::track_metadata::{KeyNotation, TrackMetadata};
+
+/**
+ * How a M3U playlist is written.
+ */
+#[derive(Default)]
+pub struct WriteOptions<'a> {
+    // Written as `#PLAYLIST` when not empty.
+    pub name: &'a str,
+    // Add BPM and key (in `notation`) as plain comment lines players ignore.
+    pub comments: bool,
+    pub notation: KeyNotation,
+    // Rewrites paths for the playlist being written.
+    pub profile: Option<&'a PathProfile>,
+    // Only `#EXTINF` and paths with forward slashes, for players that skip anything else.
+    pub plain: bool,
+}
+
+/**
+ * Write an extended M3U playlist. `#EXTINF` carries the length in whole
+ * seconds (-1 when unknown) and "Artist - Title".
+ * Paths are rewritten with the options' profile for the playlist at `playlist_path`.
+ */
+pub fn write_to<T: std::io::Write>(
+    tracks: &[TrackMetadata],
+    options: &WriteOptions,
+    playlist_path: &std::path::Path,
+    w: &mut T,
+) -> std::io::Result<()> {
+    writeln!(w, "#EXTM3U")?;
+    if !options.name.is_empty() && !options.plain {
+        writeln!(w, "#PLAYLIST:{}", single_line(options.name))?;
+    }
+
+    for track in tracks {
+        let duration = track.duration_secs().unwrap_or(track.media_segment.duration);
+        let duration = if duration > 0. { duration.round() as i64 } else { -1 };
+        writeln!(w, "#EXTINF:{},{}", duration, single_line(&display_name(track)))?;
+
+        if !options.plain {
+            for (directive, value) in [("EXTART", &track.artist), ("EXTALB", &track.album), ("EXTGENRE", &track.genre)] {
+                if !value.is_empty() {
+                    writeln!(w, "#{}:{}", directive, single_line(value))?;
+                }
+            }
+        }
+
+        if options.comments && !options.plain {
+            if let Some(bpm) = track.bpm.value {
+                writeln!(w, "# BPM: {:.2}", bpm.0)?;
+            }
+            if !track.key.raw.trim().is_empty() {
+                writeln!(w, "# Key: {}", track.key_text(options.notation))?;
+            }
+        }
+
+        let uri = &track.media_segment.uri;
+        let path = options.profile.map_or(uri.clone(), |profile| profile.apply(uri, playlist_path));
+        if options.plain {
+            writeln!(w, "{}", path.replace('\\', "/"))?;
+        } else {
+            writeln!(w, "{}", path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * "Artist - Title", or just the title when the artist is unknown.
+ */
+pub fn display_name(track: &TrackMetadata) -> String {
+    if track.artist.is_empty() {
+        track.title.clone()
+    } else {
+        format!("{} - {}", track.artist, track.title)
+    }
+}
+
+// A line break inside a value would start a new entry.
+fn single_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+/**
+ * Read the entries of a M3U or m3u8 file into rows, using only what the
+ * playlist itself says (`#EXTINF`, `#EXTART`, `#EXTALB`, `#EXTGENRE`).
+ * File URIs are decoded and relative paths resolved against the folder of `path`.
+ */
+pub fn parse(path: &std::path::Path, text: &str) -> Vec<TrackMetadata> {
+    let playlist_dir = path.parent().unwrap_or(std::path::Path::new(""));
+    let mut tracks = vec![];
+    let mut entry = TrackMetadata::default();
+    let mut display = String::new();
+
+    for line in text.lines().map(|l| l.trim_start_matches('\u{feff}').trim()) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // "#EXTINF:123 attr=\"x\",Artist - Title", the title may contain commas.
+            let (head, title) = info.split_once(',').unwrap_or((info, ""));
+            let duration = head.split_whitespace().next().and_then(|d| d.parse::<f32>().ok()).unwrap_or(-1.);
+            if duration > 0. {
+                entry.media_segment.duration = duration;
+                entry.time = crate::track_metadata::format_time(duration).into();
+            }
+            display = title.trim().to_string();
+        } else if let Some(artist) = line.strip_prefix("#EXTART:") {
+            entry.artist = artist.trim().to_string();
+        } else if let Some(album) = line.strip_prefix("#EXTALB:") {
+            entry.album = album.trim().to_string();
+        } else if let Some(genre) = line.strip_prefix("#EXTGENRE:") {
+            entry.genre = genre.trim().to_string();
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let mut track = std::mem::take(&mut entry);
+            let display = std::mem::take(&mut display);
+            track.number = tracks.len() as u32 + 1;
+            track.media_segment.uri = crate::paths::resolve(line, playlist_dir);
+
+            match display.split_once(" - ") {
+                _ if display.is_empty() => {
+                    track.title = std::path::Path::new(&track.media_segment.uri)
+                        .file_stem()
+                        .map_or(String::new(), |s| s.to_string_lossy().into_owned());
+                }
+                Some((artist, title)) if track.artist.is_empty() || track.artist == artist.trim() => {
+                    track.artist = artist.trim().to_string();
+                    track.title = title.trim().to_string();
+                }
+                _ => track.title = display,
+            }
+
+            track.media_segment.title = Some(format!("{} - {}", track.artist, track.title));
+            track.update_id();
+            tracks.push(track);
+        }
+    }
+
+    tracks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_extended_entries() {
+        let text = "\u{feff}#EXTM3U\n\
+                    #EXTINF:185,Artist A - Title, with comma\n\
+                    #EXTALB:Album\n\
+                    music/a.mp3\n\
+                    \n\
+                    /music/b.mp3\n";
+        let tracks = parse(std::path::Path::new("/lists/set.m3u8"), text);
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].artist, "Artist A");
+        assert_eq!(tracks[0].title, "Title, with comma");
+        assert_eq!(tracks[0].album, "Album");
+        assert_eq!(tracks[0].time.raw, "03:05");
+        assert_eq!(tracks[0].media_segment.uri, "/lists/music/a.mp3");
+        assert_eq!(tracks[1].title, "b");
+        assert_eq!(tracks[1].media_segment.uri, "/music/b.mp3");
+    }
+
+    #[test]
+    fn writes_directives_and_comments() {
+        let track = TrackMetadata {
+            title: "Title".to_string(),
+            artist: "Artist".to_string(),
+            bpm: "128".to_string().into(),
+            key: "8A".to_string().into(),
+            time: "03:05".to_string().into(),
+            media_segment: m3u8_rs::MediaSegment {
+                uri: "/music/a.mp3".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut out = vec![];
+        let options = WriteOptions {
+            name: "Set",
+            comments: true,
+            notation: KeyNotation::MusicalSharps,
+            ..Default::default()
+        };
+        write_to(&[track], &options, std::path::Path::new("/lists/set.m3u8"), &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "#EXTM3U\n#PLAYLIST:Set\n#EXTINF:185,Artist - Title\n#EXTART:Artist\n# BPM: 128.00\n# Key: Am\n/music/a.mp3\n"
+        );
+    }
+
+    #[test]
+    fn plain_writes_only_extinf_and_forward_slashes() {
+        let track = TrackMetadata {
+            title: "Title".to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            bpm: "128".to_string().into(),
+            time: "03:05".to_string().into(),
+            media_segment: m3u8_rs::MediaSegment {
+                uri: "C:\\Music\\a.mp3".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let options = WriteOptions {
+            name: "Set",
+            comments: true,
+            plain: true,
+            ..Default::default()
+        };
+        let mut out = vec![];
+        write_to(&[track], &options, std::path::Path::new("C:\\lists\\set.m3u"), &mut out).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "#EXTM3U\n#EXTINF:185,Artist - Title\nC:/Music/a.mp3\n");
+    }
+}
@@ -1,11 +1,33 @@
 #![windows_subsystem = "windows"]
 use std::{cell::RefCell, rc::Rc};
-use std::thread;
 use eframe::egui;
 
+mod audio_tags;
+mod bundle;
+mod collection;
+mod column_mapping;
+mod config;
+mod cue_sheet;
+mod engine_dj;
+mod folder_scan;
+mod harmonic_rules;
+mod m3u;
+mod path_rules;
+mod paths;
 mod playlist;
+mod pls;
+mod previews;
+mod reconcile;
+mod rekordbox_xml;
+mod set_sheet;
+mod settings;
+mod text_export;
 mod track_metadata;
 mod track_player;
+mod traktor_nml;
+mod virtualdj;
+mod xml;
+mod xspf;
 
 fn main() -> eframe::Result {
     let options = eframe::NativeOptions {
@@ -29,6 +51,19 @@ struct App {
     export_table: playlist::Playlist,
     selected_track: Rc<RefCell<Option<track_metadata::TrackMetadata>>>,
     player: track_player::Player,
+    import_preview: Option<column_mapping::ImportPreview>,
+    import_collection: Option<collection::Collection>,
+    folder_scan: Option<folder_scan::FolderScan>,
+    previews: Option<previews::Previews>,
+    bundle: Option<bundle::Bundle>,
+    mix_timeline: Option<cue_sheet::MixTimeline>,
+    merge_import: bool,
+    m3u_comments: bool,
+    import_error: Option<playlist::ImportError>,
+    import_warnings: Vec<playlist::ImportError>,
+    path_profiles: path_rules::PathProfiles,
+    settings: settings::Settings,
+    show_settings: bool,
 }
 
 impl Default for App {
@@ -40,6 +75,19 @@ impl Default for App {
             export_table: playlist::Playlist::new(selected_track.clone()),
             selected_track,
             player: track_player::Player::default(),
+            import_preview: None,
+            import_collection: None,
+            folder_scan: None,
+            previews: None,
+            bundle: None,
+            mix_timeline: None,
+            merge_import: false,
+            m3u_comments: false,
+            path_profiles: path_rules::PathProfiles::load(),
+            settings: settings::Settings::load(),
+            show_settings: false,
+            import_error: None,
+            import_warnings: vec![],
         }
     }
 }
@@ -51,10 +99,30 @@ impl App {
 
         if selected_track.is_none() && player_track.is_some() {
             self.player.load(None);
-        } else if selected_track.as_ref().is_some_and(|t| t.id != player_track.map_or(0, |t| t.id)) {
+        } else if selected_track.as_ref().is_some_and(|t| Some(t.id) != player_track.map(|t| t.id)) {
             self.player.load(selected_track);
         }
     }
+
+    /**
+     * Render waveform previews for the import table, replacing a run in progress.
+     */
+    fn start_previews(&mut self) {
+        if let Some(previews) = &self.previews {
+            previews.cancel();
+        }
+        self.previews = Some(previews::Previews::start(&self.import_table.tracks));
+    }
+
+    /**
+     * Show a failed export in the problems panel.
+     */
+    fn report_export(&mut self, result: std::io::Result<()>) {
+        if let Err(e) = result {
+            self.import_error = Some(playlist::ImportError::Write(e));
+            self.import_warnings.clear();
+        }
+    }
 }
 
 impl eframe::App for App {
@@ -63,7 +131,18 @@ impl eframe::App for App {
 
         self.update_player();
         let mut import_clicked = false;
+        let mut import_xml_clicked = false;
+        let mut import_nml_clicked = false;
+        let mut import_folder_clicked = false;
+        let mut import_m3u_clicked = false;
         let mut export_clicked = false;
+        let mut export_xml_clicked = false;
+        let mut export_tsv_clicked = false;
+        let mut export_bundle_clicked = false;
+        let mut export_sheet_clicked = false;
+        let mut export_vdj_clicked = false;
+        let mut export_engine_clicked = false;
+        let mut export_cue_clicked = false;
         let mut play_clicked = false;
         let mut stop_clicked = false;
 
@@ -75,8 +154,69 @@ impl eframe::App for App {
                 .vertical(|mut strip| {
                     strip.cell(|ui| {
                         ui.horizontal_centered(|ui| {
-                            import_clicked = ui.button("Import Table").clicked();
-                            export_clicked = ui.button("Export Table").clicked();
+                            ui.menu_button("Import Table", |ui| {
+                                if ui.button("Rekordbox text + playlist").clicked() {
+                                    import_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("Rekordbox XML").clicked() {
+                                    import_xml_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("Traktor NML").clicked() {
+                                    import_nml_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("Playlist (m3u8 / XSPF / PLS)").clicked() {
+                                    import_m3u_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui
+                                    .button("Music folder")
+                                    .on_hover_text(format!("Reads {} files", audio_tags::AUDIO_EXTENSIONS.join(", ")))
+                                    .clicked()
+                                {
+                                    import_folder_clicked = true;
+                                    ui.close_menu();
+                                }
+                            });
+                            ui.checkbox(&mut self.merge_import, "Add to table");
+                            ui.menu_button("Export Table", |ui| {
+                                if ui.button("m3u8 / XSPF / PLS").clicked() {
+                                    export_clicked = true;
+                                    ui.close_menu();
+                                }
+                                ui.checkbox(&mut self.m3u_comments, "m3u8 with BPM/key comments");
+                                if ui.button("Rekordbox XML").clicked() {
+                                    export_xml_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("VirtualDJ (vdjfolder)").clicked() {
+                                    export_vdj_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("Engine DJ (m3u)").clicked() {
+                                    export_engine_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("Table with metadata (tsv)").clicked() {
+                                    export_tsv_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("Set sheet (HTML / Markdown)").clicked() {
+                                    export_sheet_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("CUE sheet / tracklist").clicked() {
+                                    export_cue_clicked = true;
+                                    ui.close_menu();
+                                }
+                                if ui.button("Bundle with track files").clicked() {
+                                    export_bundle_clicked = true;
+                                    ui.close_menu();
+                                }
+                            });
+                            ui.toggle_value(&mut self.show_settings, "Settings");
                             play_clicked = ui.button("Play").clicked();
                             stop_clicked = ui.button("Stop").clicked();
 
@@ -107,6 +247,42 @@ impl eframe::App for App {
                 });
 
         });
+
+        if let Some(previews) = &mut self.previews {
+            self.import_warnings.extend(previews.poll());
+            playlist::repaint_while_busy(ctx, !previews.is_finished());
+            if previews.is_finished() {
+                self.previews = None;
+            }
+        }
+        self.import_warnings.extend(config::take_problems().into_iter().map(playlist::ImportError::Config));
+        if self.import_error.is_some() || !self.import_warnings.is_empty() {
+            egui::TopBottomPanel::bottom("import errors").resizable(true).show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Problems: {}", self.import_warnings.len() + self.import_error.iter().count()));
+                    if ui.button("Dismiss").clicked() {
+                        self.import_error = None;
+                        self.import_warnings.clear();
+                    }
+                });
+                egui::ScrollArea::vertical().max_height(150.).show(ui, |ui| {
+                    if let Some(error) = &self.import_error {
+                        ui.colored_label(egui::Color32::from_rgb(220, 60, 60), error.to_string());
+                    }
+                    for warning in self.import_warnings.iter() {
+                        ui.colored_label(egui::Color32::from_rgb(220, 180, 40), warning.to_string());
+                    }
+                });
+            });
+        }
+
+        egui::SidePanel::right("settings").show_animated(ctx, self.show_settings, |ui| {
+            ui.heading("Settings");
+            ui.separator();
+            self.path_profiles.picker_ui(ui);
+            self.settings.ui(ui);
+        });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             use egui_extras::{Size, StripBuilder};
             StripBuilder::new(ui)
@@ -120,7 +296,7 @@ impl eframe::App for App {
                             .inner_margin(egui::Margin::same(10.))
                             .show(ui, |ui| {
                                 egui::ScrollArea::horizontal().show(ui, |ui| {
-                                    self.import_table.ui(ui);
+                                    self.import_table.ui(ui, &self.settings);
                                 });
                             });
                     });
@@ -137,7 +313,7 @@ impl eframe::App for App {
                             .inner_margin(egui::Margin::same(10.))
                             .show(ui, |ui| {
                                 egui::ScrollArea::horizontal().show(ui, |ui| {
-                                    self.export_table.ui(ui);
+                                    self.export_table.ui(ui, &self.settings);
                                 });
                             });
                     });
@@ -145,45 +321,157 @@ impl eframe::App for App {
         });
 
         if import_clicked {
-            self.import_table.import();
-            // spawn threads to generate previews.
-            let tracks = std::sync::Arc::new(self.import_table.tracks.clone());
-
-            let handles: Vec<_> = tracks.iter().map(|track| {
-                let track = track.clone();
-                println!("generating preview for: {}", track.title);
-                thread::spawn(move || {
-                    let datadir = std::env::temp_dir().join("playlists");
-                    std::fs::create_dir_all(&datadir).unwrap();
-                    let filename = track.id.to_string() + track.title.as_str() + track.artist.as_str() + ".png";
-
-                    if datadir.join(filename.as_str()).exists() {
-                        return;
+            match column_mapping::ImportPreview::pick() {
+                Some(Ok(preview)) => self.import_preview = Some(preview),
+                Some(Err(e)) => self.import_error = Some(e),
+                None => {}
+            }
+        }
+
+        if let Some(preview) = &mut self.import_preview {
+            match preview.ui(ctx) {
+                column_mapping::PreviewAction::Import => {
+                    match self.import_table.import(preview, self.merge_import) {
+                        Ok(warnings) => {
+                            self.import_error = None;
+                            self.import_warnings = warnings;
+                        }
+                        Err(e) => {
+                            self.import_error = Some(e);
+                            self.import_warnings.clear();
+                        }
+                    }
+                    self.import_preview = None;
+                    self.start_previews();
+                }
+                column_mapping::PreviewAction::Cancel => self.import_preview = None,
+                column_mapping::PreviewAction::None => {}
+            }
+        }
+
+        if import_xml_clicked || import_nml_clicked {
+            let picked = if import_xml_clicked { rekordbox_xml::pick() } else { traktor_nml::pick() };
+            match picked {
+                Some(Ok(collection)) => self.import_collection = Some(collection),
+                Some(Err(e)) => self.import_error = Some(e),
+                None => {}
+            }
+        }
+
+        if let Some(collection) = &mut self.import_collection {
+            match collection.ui(ctx) {
+                collection::CollectionAction::Load(i) => {
+                    let tracks = collection.playlist_tracks(i);
+                    let duplicates = self.import_table.load(tracks, &collection.playlists[i].name, self.merge_import);
+                    self.import_error = None;
+                    self.import_warnings.clear();
+                    if duplicates > 0 {
+                        self.import_warnings.push(playlist::ImportError::Duplicates(duplicates));
                     }
+                    self.import_collection = None;
+                    self.start_previews();
+                }
+                collection::CollectionAction::Cancel => self.import_collection = None,
+                collection::CollectionAction::None => {}
+            }
+        }
 
-                    let path = std::path::Path::new(&track.media_segment.uri);
-                    let decoder = rodio::Decoder::new(std::fs::File::open(path).unwrap());
+        if import_folder_clicked && self.folder_scan.is_none() {
+            self.folder_scan = folder_scan::FolderScan::pick();
+        }
 
-                    audio_visualizer::waveform::png_file::waveform_static_png_visualize(
-                        &decoder.unwrap().collect::<Vec<i16>>(),
-                        audio_visualizer::Channels::Mono,
-                        datadir.to_str().unwrap(),
-                        filename.as_str(),
-                    );
-                })
-            }).collect();
+        if import_m3u_clicked && self.folder_scan.is_none() {
+            match folder_scan::FolderScan::pick_playlist() {
+                Some(Ok(scan)) => self.folder_scan = Some(scan),
+                Some(Err(e)) => self.import_error = Some(e),
+                None => {}
+            }
+        }
 
-            for handle in handles {
-                if let Err(e) = handle.join() { eprintln!("Thread encountered an error: {:?}", e) }
+        if let Some(scan) = &mut self.folder_scan {
+            if let Some((tracks, mut warnings)) = scan.poll() {
+                let source = scan.root.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+                let duplicates = self.import_table.load(tracks, &source, self.merge_import);
+                if duplicates > 0 {
+                    warnings.push(playlist::ImportError::Duplicates(duplicates));
+                }
+                self.import_error = None;
+                self.import_warnings = warnings;
+                self.folder_scan = None;
+                self.start_previews();
+            } else if !scan.ui(ctx) {
+                scan.cancel();
+                self.folder_scan = None;
+            }
+        }
+
+        if self.path_profiles.editing {
+            self.path_profiles.editor_ui(ctx);
+        }
+
+        if let Some(reconciliation) = &mut self.import_table.reconciliation {
+            if !reconciliation.ui(ctx, &mut self.import_table.tracks) {
+                self.import_table.reconciliation = None;
+                self.start_previews();
             }
         }
 
         if export_clicked {
-            self.export_table.export();
+            let result = self.export_table.export(self.path_profiles.selected(), self.m3u_comments, &self.settings);
+            self.report_export(result);
+        }
+
+        if export_xml_clicked {
+            let result = rekordbox_xml::export(&self.export_table.tracks);
+            self.report_export(result);
+        }
+
+        if export_tsv_clicked {
+            let result = text_export::export(&self.export_table.tracks);
+            self.report_export(result);
+        }
+
+        if export_vdj_clicked {
+            let result = virtualdj::export(&self.export_table.tracks, self.path_profiles.selected(), self.settings.key_notation);
+            self.report_export(result);
+        }
+
+        if export_engine_clicked {
+            let result = engine_dj::export(&self.export_table.tracks, self.path_profiles.selected());
+            self.report_export(result);
+        }
+
+        if export_sheet_clicked {
+            let result = set_sheet::export(&self.export_table.tracks, &self.settings);
+            self.report_export(result);
+        }
+
+        if export_cue_clicked {
+            self.mix_timeline = Some(cue_sheet::MixTimeline::new(&self.export_table.tracks));
+        }
+
+        if let Some(timeline) = &mut self.mix_timeline {
+            if !timeline.ui(ctx) {
+                self.mix_timeline = None;
+            }
+        }
+
+        if export_bundle_clicked && self.bundle.is_none() {
+            self.bundle = bundle::Bundle::pick(&self.export_table.tracks);
+        }
+
+        if let Some(bundle) = &mut self.bundle {
+            bundle.poll();
+            if !bundle.ui(ctx) {
+                bundle.cancel();
+                self.bundle = None;
+            }
         }
 
-        let arrow_down = ctx.input(|i| i.clone().consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown));
-        let arrow_up = ctx.input(|i| i.clone().consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp));
+        // Leave the keys to text fields while one has focus.
+        let typing = ctx.wants_keyboard_input();
+        let arrow_down = !typing && ctx.input(|i| i.clone().consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown));
+        let arrow_up = !typing && ctx.input(|i| i.clone().consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp));
         if arrow_down {
             if let Some(t) = self.selected_track.borrow().as_ref() {
                 self.import_table.maybe_move_track_down(t);
@@ -197,7 +485,7 @@ impl eframe::App for App {
         }
         
 
-        let space = ctx.input(|i| i.clone().consume_key(egui::Modifiers::NONE, egui::Key::Space));
+        let space = !typing && ctx.input(|i| i.clone().consume_key(egui::Modifiers::NONE, egui::Key::Space));
         if space {
             if let Some(t) = self.selected_track.borrow().as_ref() {
                 if let Some(track) = self.import_table.maybe_remove_track(t.id) {
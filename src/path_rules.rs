@@ -0,0 +1,337 @@
+const PROFILES_CONFIG: &str = "path_profiles";
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Separator {
+    Forward,
+    Backward,
+}
+
+/**
+*
+* A single rewrite step applied to every exported path.
+*
+*/
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum PathRule {
+    ReplacePrefix { from: String, to: String },
+    RelativeToPlaylist,
+    Separators(Separator),
+}
+
+impl PathRule {
+    fn label(&self) -> &'static str {
+        match self {
+            PathRule::ReplacePrefix { .. } => "Replace prefix",
+            PathRule::RelativeToPlaylist => "Relative to playlist",
+            PathRule::Separators(_) => "Separators",
+        }
+    }
+
+    pub fn apply(&self, path: &str, playlist_dir: &std::path::Path) -> String {
+        match self {
+            PathRule::ReplacePrefix { from, to } => match path.strip_prefix(from.as_str()) {
+                Some(rest) if !from.is_empty() => format!("{}{}", to, rest),
+                _ => path.to_string(),
+            },
+            PathRule::RelativeToPlaylist => relative_to(path, &playlist_dir.to_string_lossy()),
+            PathRule::Separators(Separator::Forward) => path.replace('\\', "/"),
+            PathRule::Separators(Separator::Backward) => path.replace('/', "\\"),
+        }
+    }
+}
+
+/**
+*
+* Named list of rules, e.g. "USB" or "Laptop B".
+*
+*/
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct PathProfile {
+    pub name: String,
+    pub rules: Vec<PathRule>,
+}
+
+impl PathProfile {
+    /**
+     * Rewrite a track path for a playlist that will be written to `playlist_path`.
+     */
+    pub fn apply(&self, path: &str, playlist_path: &std::path::Path) -> String {
+        let playlist_dir = playlist_path.parent().unwrap_or(std::path::Path::new(""));
+        self.rules
+            .iter()
+            .fold(path.to_string(), |path, rule| rule.apply(&path, playlist_dir))
+    }
+}
+
+/**
+ * Express `path` relative to `base_dir`. Works on the text of the paths
+ * so it handles windows paths on other systems too. Paths on another
+ * drive or share, or absolute against relative, are returned unchanged.
+ * Windows paths compare without case, like their file systems.
+ */
+pub fn relative_to(path: &str, base_dir: &str) -> String {
+    let split = |p: &str| -> Vec<String> {
+        p.replace('\\', "/")
+            .split('/')
+            .filter(|c| !c.is_empty() && *c != ".")
+            .map(|c| c.to_string())
+            .collect()
+    };
+
+    // "c:" for drives, "//server/share" for shares, "/" for other absolute paths.
+    let root = |p: &str| -> String {
+        match p.as_bytes() {
+            [drive, b':', ..] if drive.is_ascii_alphabetic() => format!("{}:", drive.to_ascii_lowercase() as char),
+            [b'/' | b'\\', b'/' | b'\\', ..] => {
+                let share: Vec<String> = split(p).iter().take(2).map(|c| c.to_lowercase()).collect();
+                format!("//{}", share.join("/"))
+            }
+            [b'/' | b'\\', ..] => "/".to_string(),
+            _ => String::new(),
+        }
+    };
+
+    let path_root = root(path);
+    if path_root != root(base_dir) {
+        return path.to_string();
+    }
+
+    let windows = path_root.len() > 1 || path.contains('\\');
+    let path_parts = split(path);
+    let base_parts = split(base_dir);
+    let common = path_parts
+        .iter()
+        .zip(base_parts.iter())
+        .take_while(|(a, b)| if windows { a.eq_ignore_ascii_case(b) } else { a == b })
+        .count();
+
+    let mut parts: Vec<&str> = vec![".."; base_parts.len() - common];
+    parts.extend(path_parts[common..].iter().map(|p| p.as_str()));
+    parts.join("/")
+}
+
+/**
+*
+* Saved profiles plus the one selected for exports.
+*
+*/
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub struct PathProfiles {
+    pub profiles: Vec<PathProfile>,
+    pub selected: Option<String>,
+    #[serde(skip)]
+    pub editing: bool,
+    // Name being typed for a profile, applied when the field loses focus.
+    #[serde(skip)]
+    renaming: Option<(usize, String)>,
+    #[serde(skip)]
+    rename_error: Option<String>,
+}
+
+impl PathProfiles {
+    pub fn load() -> Self {
+        crate::config::load(PROFILES_CONFIG)
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = crate::config::save(PROFILES_CONFIG, self) {
+            crate::config::report(format!("Could not save path profiles: {}", e));
+        }
+    }
+
+    pub fn selected(&self) -> Option<&PathProfile> {
+        self.selected
+            .as_ref()
+            .and_then(|name| self.profiles.iter().find(|p| &p.name == name))
+    }
+
+    /**
+     * Rename a profile, keeping it selected. Names must be unique and not empty.
+     */
+    fn rename(&mut self, index: usize, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Profile names can't be empty.".to_string());
+        }
+        if self.profiles.iter().enumerate().any(|(i, p)| i != index && p.name == name) {
+            return Err(format!("There already is a profile named \"{}\".", name));
+        }
+
+        let old = std::mem::replace(&mut self.profiles[index].name, name.to_string());
+        if self.selected.as_deref() == Some(old.as_str()) {
+            self.selected = Some(name.to_string());
+        }
+        Ok(())
+    }
+
+    /**
+     * Profile picker for the settings panel.
+     */
+    pub fn picker_ui(&mut self, ui: &mut eframe::egui::Ui) {
+        let before = self.selected.clone();
+        ui.horizontal(|ui| {
+            ui.label("Export paths:");
+            eframe::egui::ComboBox::from_id_salt("path profile")
+                .selected_text(self.selected.as_deref().unwrap_or("Paths as imported"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.selected, None, "Paths as imported");
+                    for profile in self.profiles.iter() {
+                        ui.selectable_value(&mut self.selected, Some(profile.name.clone()), profile.name.as_str());
+                    }
+                });
+
+            if ui.button("Edit paths").clicked() {
+                self.editing = true;
+            }
+        });
+
+        if before != self.selected {
+            self.save();
+        }
+    }
+
+    /**
+     * Profile editor window.
+     */
+    pub fn editor_ui(&mut self, ctx: &eframe::egui::Context) {
+        use eframe::egui;
+        let mut open = self.editing;
+        let mut remove_profile = None;
+        let mut apply_rename = false;
+
+        egui::Window::new("Export path profiles")
+            .open(&mut open)
+            .default_width(500.)
+            .show(ctx, |ui| {
+                for (p, profile) in self.profiles.iter_mut().enumerate() {
+                    ui.push_id(p, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Name:");
+                            let mut name = match &self.renaming {
+                                Some((i, name)) if *i == p => name.clone(),
+                                _ => profile.name.clone(),
+                            };
+                            let response = ui.text_edit_singleline(&mut name);
+                            if response.changed() {
+                                self.renaming = Some((p, name));
+                            }
+                            if response.lost_focus() {
+                                apply_rename = true;
+                            }
+                            if ui.button("Remove profile").clicked() {
+                                remove_profile = Some(p);
+                            }
+                        });
+
+                        let mut remove_rule = None;
+                        for (r, rule) in profile.rules.iter_mut().enumerate() {
+                            ui.push_id(r, |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label(rule.label());
+                                    match rule {
+                                        PathRule::ReplacePrefix { from, to } => {
+                                            ui.text_edit_singleline(from);
+                                            ui.label("→");
+                                            ui.text_edit_singleline(to);
+                                        }
+                                        PathRule::RelativeToPlaylist => {}
+                                        PathRule::Separators(separator) => {
+                                            ui.selectable_value(separator, Separator::Forward, "/");
+                                            ui.selectable_value(separator, Separator::Backward, "\\");
+                                        }
+                                    }
+                                    if ui.button("✖").clicked() {
+                                        remove_rule = Some(r);
+                                    }
+                                });
+                            });
+                        }
+                        if let Some(r) = remove_rule {
+                            profile.rules.remove(r);
+                        }
+
+                        ui.horizontal(|ui| {
+                            if ui.button("+ Replace prefix").clicked() {
+                                profile.rules.push(PathRule::ReplacePrefix {
+                                    from: String::new(),
+                                    to: String::new(),
+                                });
+                            }
+                            if ui.button("+ Relative to playlist").clicked() {
+                                profile.rules.push(PathRule::RelativeToPlaylist);
+                            }
+                            if ui.button("+ Separators").clicked() {
+                                profile.rules.push(PathRule::Separators(Separator::Forward));
+                            }
+                        });
+                        ui.separator();
+                    });
+                }
+
+                if let Some(error) = &self.rename_error {
+                    ui.colored_label(egui::Color32::from_rgb(220, 60, 60), error);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("New profile").clicked() {
+                        let name = (self.profiles.len() + 1..)
+                            .map(|n| format!("Profile {}", n))
+                            .find(|name| self.profiles.iter().all(|p| &p.name != name))
+                            .unwrap_or_default();
+                        self.profiles.push(PathProfile { name, rules: vec![] });
+                    }
+                    if ui.button("Save").clicked() {
+                        self.save();
+                    }
+                });
+            });
+
+        // Closing the window keeps a name that is still being typed.
+        if apply_rename || !open {
+            if let Some((p, name)) = self.renaming.take() {
+                self.rename_error = self.rename(p, &name).err();
+            }
+        }
+
+        if let Some(p) = remove_profile {
+            let removed = self.profiles.remove(p);
+            if self.selected.as_ref() == Some(&removed.name) {
+                self.selected = None;
+            }
+            self.renaming = None;
+        }
+
+        if self.editing && !open {
+            self.save();
+        }
+        self.editing = open;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::relative_to;
+
+    #[test]
+    fn relative_on_unix() {
+        assert_eq!(relative_to("/home/me/music/a.mp3", "/home/me/lists"), "../music/a.mp3");
+        assert_eq!(relative_to("/home/me/lists/a.mp3", "/home/me/lists"), "a.mp3");
+        assert_eq!(relative_to("/media/usb/a.mp3", "/home/me/lists"), "../../../media/usb/a.mp3");
+        assert_eq!(relative_to("/Music/a.mp3", "/music/lists"), "../../Music/a.mp3");
+    }
+
+    #[test]
+    fn relative_on_windows() {
+        assert_eq!(relative_to("C:\\Music\\a.mp3", "c:\\Users\\me"), "../../Music/a.mp3");
+        assert_eq!(relative_to("C:\\MUSIC\\a.mp3", "C:\\Music\\Lists"), "../a.mp3");
+        assert_eq!(relative_to("\\\\nas\\music\\a.mp3", "\\\\NAS\\Music\\lists"), "../a.mp3");
+    }
+
+    #[test]
+    fn different_roots_stay_absolute() {
+        assert_eq!(relative_to("D:\\Music\\a.mp3", "C:\\Lists"), "D:\\Music\\a.mp3");
+        assert_eq!(relative_to("/music/a.mp3", "C:\\Lists"), "/music/a.mp3");
+        assert_eq!(relative_to("\\\\nas\\music\\a.mp3", "\\\\other\\music"), "\\\\nas\\music\\a.mp3");
+        assert_eq!(relative_to("music/a.mp3", "/home/me"), "music/a.mp3");
+    }
+}
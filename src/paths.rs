@@ -0,0 +1,88 @@
+/*!
+*
+* Conversions between file paths and the URIs DJ software stores them as.
+*
+*/
+
+/**
+ * Turn a `file://` URI (as written by Rekordbox, XSPF, etc.) into a plain path.
+ * Anything that isn't a file URI is returned unchanged.
+ */
+pub fn uri_to_path(uri: &str) -> String {
+    let Some(rest) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let decoded = percent_encoding::percent_decode_str(rest).decode_utf8_lossy().into_owned();
+
+    // "/C:/Music/track.mp3" is a windows path, drop the leading slash.
+    match decoded.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => decoded[1..].to_string(),
+        _ => decoded,
+    }
+}
+
+// Characters Rekordbox and friends percent-encode in file URIs.
+const URI_ENCODE: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/**
+ * Turn a path into a `file://localhost/` URI, the form Rekordbox expects.
+ */
+pub fn path_to_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let path = path.strip_prefix('/').unwrap_or(&path);
+    format!("file://localhost/{}", percent_encoding::utf8_percent_encode(path, URI_ENCODE))
+}
+
+/**
+ * Turn a path into a URI, or a relative URI reference for relative paths.
+ */
+pub fn path_to_uri_reference(path: &str) -> String {
+    if is_absolute(path) {
+        path_to_uri(path)
+    } else {
+        percent_encoding::utf8_percent_encode(&path.replace('\\', "/"), URI_ENCODE).to_string()
+    }
+}
+
+/**
+ * Whether a path is absolute on any system, so windows paths are
+ * recognised on other systems too.
+ */
+pub fn is_absolute(path: &str) -> bool {
+    match path.as_bytes() {
+        [b'/' | b'\\', ..] => true,
+        [drive, b':', ..] => drive.is_ascii_alphabetic(),
+        _ => false,
+    }
+}
+
+/**
+ * Resolve a location read from a playlist file: file URIs are decoded
+ * and relative paths are taken from the playlist's folder.
+ */
+pub fn resolve(location: &str, playlist_dir: &std::path::Path) -> String {
+    let path = uri_to_path(location.trim());
+    if path.is_empty() || is_absolute(&path) || path.contains("://") {
+        path
+    } else {
+        playlist_dir.join(percent_encoding::percent_decode_str(&path).decode_utf8_lossy().as_ref())
+            .to_string_lossy()
+            .into_owned()
+    }
+}
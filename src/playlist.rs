@@ -1,5 +1,88 @@
 use std::{cell::RefCell, rc::Rc};
-use crate::track_metadata::TrackMetadata;
+use crate::column_mapping::ImportPreview;
+use crate::path_rules::PathProfile;
+use crate::reconcile::Reconciliation;
+use crate::settings::Settings;
+use crate::track_metadata::{TrackId, TrackMetadata};
+
+// Playlist files that can be imported or matched against an imported table.
+pub const PLAYLIST_EXTENSIONS: [&str; 4] = ["m3u8", "m3u", "xspf", "pls"];
+
+// Index of the BPM and Key columns in Playlist::ui.
+const BPM_COLUMN: usize = 3;
+const KEY_COLUMN: usize = 4;
+
+/**
+ * Draw a value that failed to parse at import.
+ */
+pub fn show_invalid(ui: &mut eframe::egui::Ui, text: &str) {
+    ui.colored_label(eframe::egui::Color32::from_rgb(230, 140, 30), format!("⚠ {}", text))
+        .on_hover_text("Could not parse this value");
+}
+
+/**
+ * Keep repainting while background work is running, so progress shows without user input.
+ */
+pub fn repaint_while_busy(ctx: &eframe::egui::Context, busy: bool) {
+    if busy {
+        ctx.request_repaint();
+    }
+}
+
+/**
+*
+* Everything that can go wrong while importing or exporting a playlist.
+* Row errors are collected as warnings, the rest abort the import.
+*
+*/
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    Csv(csv::Error),
+    CsvRow { line: u64, error: csv::Error },
+    Xml(String),
+    Pls(String),
+    Tags { path: String, error: String },
+    Preview { path: String, error: String },
+    Duplicates(usize),
+    Mismatch { rows: usize, segments: usize },
+    Write(std::io::Error),
+    Config(String),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "Could not read file: {}", e),
+            ImportError::Csv(e) => write!(f, "Could not read csv: {}", e),
+            ImportError::CsvRow { line, error } => write!(f, "Skipped row on line {}: {}", line, error),
+            ImportError::Xml(e) => write!(f, "Could not parse xml: {}", e),
+            ImportError::Pls(e) => write!(f, "Could not parse pls: {}", e),
+            ImportError::Tags { path, error } => write!(f, "Could not read tags of {}: {}", path, error),
+            ImportError::Preview { path, error } => write!(f, "Could not render waveform of {}: {}", path, error),
+            ImportError::Duplicates(count) => write!(f, "Skipped {} duplicate tracks.", count),
+            ImportError::Mismatch { rows, segments } => {
+                write!(f, "Csv has {} rows but the playlist has {} entries.", rows, segments)
+            }
+            ImportError::Write(e) => write!(f, "Could not write file: {}", e),
+            ImportError::Config(problem) => write!(f, "{}", problem),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(e: std::io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+impl From<csv::Error> for ImportError {
+    fn from(e: csv::Error) -> Self {
+        ImportError::Csv(e)
+    }
+}
 
 /**
 *
@@ -9,6 +92,8 @@ use crate::track_metadata::TrackMetadata;
 */
 pub struct Playlist {
     pub tracks: Vec<TrackMetadata>,
+    pub reconciliation: Option<Reconciliation>,
+    source_filter: Option<String>,
     ui_id: eframe::egui::Id,
     selected_track: Rc<RefCell<Option<TrackMetadata>>>,
 }
@@ -17,6 +102,8 @@ impl Playlist {
     pub fn new(selected_track: Rc<RefCell<Option<TrackMetadata>>>) -> Self {
         Self {
             tracks: vec![],
+            reconciliation: None,
+            source_filter: None,
             ui_id: eframe::egui::Id::new(rand::random::<u64>()),
             selected_track,
         }
@@ -25,7 +112,7 @@ impl Playlist {
     /**
      * Remove track and return it if contained.
      */
-    pub fn maybe_remove_track(&mut self, track_id: u32) -> Option<TrackMetadata> {
+    pub fn maybe_remove_track(&mut self, track_id: TrackId) -> Option<TrackMetadata> {
         let index = self.tracks.iter().position(|track| track.id == track_id);
         if let Some(index) = index {
             Some(self.tracks.remove(index))
@@ -57,138 +144,160 @@ impl Playlist {
     }
 
     /**
-     * Import track metadata from csv file and a corresponding m3u8 file.
+     * Put imported tracks in the table, tagging them with their source.
+     * A file can only be in the table once: repeats within the import and,
+     * when merging, tracks already in the table are skipped and their count
+     * is returned.
      */
-    pub fn import(&mut self) {
-        self.tracks = vec![];
-        // Import the playlist from a csv file.
-        let file = rfd::FileDialog::new()
-            .add_filter("csv", &["txt", "csv"])
-            .set_directory("/")
-            .pick_file();
+    pub fn load(&mut self, tracks: Vec<TrackMetadata>, source: &str, merge: bool) -> usize {
+        if !merge {
+            self.tracks = vec![];
+            self.reconciliation = None;
+            self.source_filter = None;
+        }
 
-        if let Some(file) = file {
-            println!("Importing playlist from file: {:?}", file);
-            let r = csv::ReaderBuilder::default()
-                .has_headers(true)
-                .delimiter(b'\t')
-                .from_path(file);
-
-            if let Ok(mut reader) = r {
-                reader.deserialize().for_each(|result| {
-                    if let Ok(track) = result {
-                        self.tracks.push(track);
-                    } else {
-                        println!("Error deserializing track: {:?}", result.err());
-                    }
-                });
-            } else {
-                println!("Error reading file: {:?}", r.err());
+        let mut ids: std::collections::HashSet<TrackId> = self.tracks.iter().map(|t| t.id).collect();
+        let mut duplicates = 0;
+        for mut track in tracks {
+            track.source = source.to_string();
+            track.update_id();
+            if !ids.insert(track.id) {
+                duplicates += 1;
+                continue;
             }
+            self.tracks.push(track);
         }
 
-        let file = rfd::FileDialog::new()
-            .add_filter("m3u8", &["m3u8"])
-            .set_directory("/")
-            .pick_file();
+        duplicates
+    }
 
-        if let Some(file) = file {
-            println!("Importing playlist from file: {:?}", file);
-            let bytes = std::fs::read(file).unwrap();
-            match m3u8_rs::parse_playlist(&bytes) {
-                Result::Ok((_, m3u8_rs::Playlist::MediaPlaylist(pl))) => {
-                    if pl.segments.len() != self.tracks.len() {
-                        println!("{} != {}", pl.segments.len(), self.tracks.len());
-                        panic!("Length of segments and tracks differ.");
-                    }
-
-                    for (i, segment) in pl.segments.iter().enumerate() {
-                        self.tracks[i].media_segment = segment.clone();
-                    }
-                }
-                Result::Err(e) => panic!("Parsing error: \n{}", e),
-                _ => println!("Unknown playlist type"),
+    pub fn sources(&self) -> Vec<String> {
+        let mut sources: Vec<String> = vec![];
+        for track in self.tracks.iter() {
+            if !sources.contains(&track.source) {
+                sources.push(track.source.clone());
             }
         }
+        sources
     }
 
     /**
-     *
-     * Export the playlist to a m3u8 file.
-     *
+     * Import track metadata from a previewed csv file and a corresponding m3u8 file.
+     * Returns non-fatal problems (skipped rows, unmatched entries) on success.
      */
-    pub fn export(&mut self) {
-        let file = rfd::FileDialog::new()
-            .add_filter("m3u8", &["m3u8"])
-            .set_directory("/")
-            .save_file();
+    pub fn import(&mut self, preview: &ImportPreview, merge: bool) -> Result<Vec<ImportError>, ImportError> {
+        let (mut tracks, mut warnings) = preview.tracks(None);
+        let mut reconciliation = None;
+
+        // Our own exports carry a Location column and need no playlist file.
+        let has_files = !tracks.is_empty() && tracks.iter().all(|t| !t.media_segment.uri.is_empty());
+        let file = if has_files {
+            None
+        } else {
+            rfd::FileDialog::new()
+                .add_filter("Playlists", &PLAYLIST_EXTENSIONS)
+                .add_filter("m3u8", &["m3u8", "m3u"])
+                .add_filter("XSPF", &["xspf"])
+                .add_filter("PLS", &["pls"])
+                .set_directory("/")
+                .pick_file()
+        };
 
         if let Some(file) = file {
-            println!("Exporting playlist to file: {:?}", file);
-            let mut playlist = m3u8_rs::MediaPlaylist::default();
-            println!("p: {:?}", playlist);
-
-            let mut duration = 0.;
-            for track in self.tracks.iter() {
-                playlist.segments.push(track.media_segment.clone());
-                duration += track.media_segment.duration;
+            let segments = read_segments(&file)?;
+            if segments.len() != tracks.len() {
+                warnings.push(ImportError::Mismatch {
+                    rows: tracks.len(),
+                    segments: segments.len(),
+                });
             }
-            playlist.target_duration = duration as u64;
-
-            let f = std::fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(file);
-
-            match f {
-                Ok(mut f) => {
-                    match self.write_to(&mut f) {
-                        Ok(_) => println!("Playlist written to file."),
-                        Err(e) => println!("Error writing playlist: {:?}", e),
-                    }
-                }
-                Err(e) => println!("Error opening file: {:?}", e),
+
+            let matched = crate::reconcile::match_segments(&mut tracks, segments);
+            if !matched.is_empty() {
+                reconciliation = Some(matched);
             }
+        }
 
+        let source = preview.path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        let duplicates = self.load(tracks, &source, merge);
+        if duplicates > 0 {
+            warnings.push(ImportError::Duplicates(duplicates));
         }
+        self.reconciliation = reconciliation;
+
+        Ok(warnings)
     }
 
-    /** 
-    * Partial copy paste from m3u8_rs
-    * keeping only things we need.
-    */
-    pub fn write_to<T: std::io::Write>(&self, w: &mut T) -> std::io::Result<()> {
-        writeln!(w, "#EXTM3U")?;
-
-        for track in &self.tracks {
-            match m3u8_rs::WRITE_OPT_FLOAT_PRECISION.load(core::sync::atomic::Ordering::Relaxed) {
-                core::usize::MAX => {
-                    write!(w, "#EXTINF:{},", track.media_segment.duration)?;
-                }
-                n => {
-                    write!(w, "#EXTINF:{:.*},", n, track.media_segment.duration)?;
-                }
-            };
+    /**
+     *
+     * Export the playlist to a m3u8, XSPF or PLS file, by extension.
+     *
+     */
+    pub fn export(&mut self, profile: Option<&PathProfile>, comments: bool, settings: &Settings) -> std::io::Result<()> {
+        let file = rfd::FileDialog::new()
+            .add_filter("m3u8", &["m3u8", "m3u"])
+            .add_filter("XSPF", &["xspf"])
+            .add_filter("PLS", &["pls"])
+            .set_directory("/")
+            .save_file();
 
-            if let Some(ref v) = track.media_segment.title {
-                writeln!(w, "{}", v)?;
-            } else {
-                writeln!(w)?;
-            }
+        let Some(file) = file else {
+            return Ok(());
+        };
 
-            writeln!(w, "{}", track.media_segment.uri)?;
-        }
+        let name = file.file_stem().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        let mut f = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&file)?;
 
-        Ok(())
+        let extension = file.extension().map_or(String::new(), |e| e.to_string_lossy().to_ascii_lowercase());
+        match extension.as_str() {
+            "xspf" => crate::xspf::write_to(&self.tracks, &name, profile, &file, &mut f),
+            "pls" => crate::pls::write_to(&self.tracks, profile, &file, &mut f),
+            _ => {
+                let options = crate::m3u::WriteOptions {
+                    name: &name,
+                    comments,
+                    notation: settings.key_notation,
+                    profile,
+                    plain: false,
+                };
+                crate::m3u::write_to(&self.tracks, &options, &file, &mut f)
+            }
+        }
     }
 
-    pub fn ui(&mut self, ui: &mut eframe::egui::Ui) {
+    pub fn ui(&mut self, ui: &mut eframe::egui::Ui, settings: &Settings) {
         ui.push_id(self.ui_id, |ui| {
             use eframe::egui::{Align, Layout};
             use egui_extras::{Column, TableBuilder};
             ui.style_mut().interaction.selectable_labels = false;
 
+            let sources = self.sources();
+            if sources.len() > 1 {
+                ui.horizontal(|ui| {
+                    ui.label("Source:");
+                    eframe::egui::ComboBox::from_id_salt("source filter")
+                        .selected_text(self.source_filter.as_deref().unwrap_or("All"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.source_filter, None, "All");
+                            for source in sources.iter() {
+                                ui.selectable_value(&mut self.source_filter, Some(source.clone()), source);
+                            }
+                        });
+                });
+            } else {
+                self.source_filter = None;
+            }
+
+            let target_bpm = settings.target_bpm(self.selected_track.borrow().as_ref());
+
+            let visible: Vec<usize> = (0..self.tracks.len())
+                .filter(|i| self.source_filter.as_ref().is_none_or(|s| &self.tracks[*i].source == s))
+                .collect();
+
             let mut table = TableBuilder::new(ui)
                 .id_salt(self.ui_id)
                 .cell_layout(Layout::left_to_right(Align::Center))
@@ -209,8 +318,9 @@ impl Playlist {
                         .at_least(30.),
                 )
                 .column(Column::initial(50.).resizable(false).clip(false)) // BPM
-                .column(Column::initial(40.).at_least(40.).resizable(false).clip(false)) // Key
+                .column(Column::initial(70.).at_least(45.).resizable(true).clip(true)) // Key
                 .column(Column::initial(40.).resizable(false).clip(false)) // Time
+                .column(Column::initial(75.).resizable(false).clip(false)) // Date Added
                 .column(
                     // My Tag
                     Column::initial(70.)
@@ -225,6 +335,13 @@ impl Playlist {
                         .clip(true)
                         .at_least(30.),
                 )
+                .column(
+                    // Source
+                    Column::initial(70.)
+                        .resizable(true)
+                        .clip(true)
+                        .at_least(30.),
+                )
                 .column(Column::remainder().clip(false).at_least(30.0)) // Path
                 .striped(true);
 
@@ -233,7 +350,7 @@ impl Playlist {
             table
                 .header(30.0, |mut header| {
                     [
-                        "#", "Title", "Artist", "BPM", "Key", "Time", "My Tag", "Message", "Path",
+                        "#", "Title", "Artist", "BPM", "Key", "Time", "Date Added", "My Tag", "Message", "Source", "Path",
                     ]
                     .iter()
                     .for_each(|&label| {
@@ -243,8 +360,8 @@ impl Playlist {
                     });
                 })
                 .body(|body| {
-                    body.rows(24.0, self.tracks.len(), |mut row| {
-                        let row_index = row.index();
+                    body.rows(24.0, visible.len(), |mut row| {
+                        let row_index = visible[row.index()];
                         let track = &self.tracks[row_index];
                         {
                             self.selected_track.borrow().as_ref().inspect(|t| {
@@ -252,23 +369,42 @@ impl Playlist {
                             });
                         }
                         let columns = [
-                            track.id.to_string(),
+                            track.number.to_string(),
                             track.title.to_string(),
                             track.artist.to_string(),
                             track.bpm.to_string(),
-                            track.key.to_string(),
+                            track.key_display(settings.key_notation, target_bpm),
                             track.time.to_string(),
+                            track.date_added.to_string(),
                             track.my_tag.to_string(),
                             track.message.to_string(),
+                            track.source.to_string(),
                             track.media_segment.uri.to_string(),
                         ];
 
-                        for col_data in columns.iter() {
+                        let invalid = [
+                            false,
+                            false,
+                            false,
+                            track.bpm.is_invalid(),
+                            track.key.is_invalid(),
+                            track.time.is_invalid(),
+                            track.date_added.is_invalid(),
+                            false,
+                            false,
+                            false,
+                            false,
+                        ];
+
+                        for (i, col_data) in columns.iter().enumerate() {
                             row.col(|ui| {
-                                if col_data == &track.key {
-                                    self.show_key_col(ui, track);
-                                }
-                                else {
+                                if invalid[i] {
+                                    show_invalid(ui, col_data);
+                                } else if i == BPM_COLUMN {
+                                    self.show_bpm_col(ui, track, col_data, settings);
+                                } else if i == KEY_COLUMN {
+                                    self.show_key_col(ui, track, col_data, settings, target_bpm);
+                                } else {
                                     ui.label(col_data);
                                 }
                             });
@@ -279,22 +415,71 @@ impl Playlist {
         });
     }
 
-    fn show_key_col(&self, ui: &mut eframe::egui::Ui, track: &TrackMetadata) {
-        let label = eframe::egui::widgets::Label::new(&track.key);
-        if let Some(selected_track) = self.selected_track.borrow().as_ref() {
-            let key_compare = crate::track_metadata::compare_keys(&selected_track.key, &track.key);
-            if let Ok(key_compare) = key_compare {
-                if let Some(color) = crate::track_metadata::color_from_key_compare(key_compare) {
-                    eframe::egui::Frame::default().fill(color).show(ui, |ui| {
-                        ui.set_width(30.);
-                        ui.colored_label(eframe::egui::Color32::from_rgb(255, 255, 255), label.text());
-                    });
-                } else {
-                    ui.add(label);
-                }
-            }
+    fn show_key_col(
+        &self,
+        ui: &mut eframe::egui::Ui,
+        track: &TrackMetadata,
+        text: &str,
+        settings: &Settings,
+        target_bpm: Option<f32>,
+    ) {
+        let label = eframe::egui::widgets::Label::new(text);
+        let selected_key = self.selected_track.borrow().as_ref().and_then(|t| t.effective_key(target_bpm));
+        let rule = match (selected_key, track.effective_key(target_bpm), settings.rules()) {
+            (Some(selected_key), Some(key), Some(rules)) => crate::track_metadata::compare_keys(&selected_key, &key, rules),
+            _ => None,
+        };
+
+        let response = if let Some(color) = rule.and_then(|rule| rule.color()) {
+            eframe::egui::Frame::default()
+                .fill(color)
+                .show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    ui.colored_label(eframe::egui::Color32::from_rgb(255, 255, 255), label.text());
+                })
+                .response
         } else {
-            ui.add(label);
+            ui.add(label)
+        };
+
+        if let Some(key) = track.key.value {
+            let notations = format!("{} / {} / {}", key, key.to_open_key(), key.to_musical(false));
+            match rule {
+                Some(rule) => response.on_hover_text(format!("{} ({})\n{}", rule.name, rule.result.label(), notations)),
+                None => response.on_hover_text(notations),
+            };
+        }
+    }
+
+    fn show_bpm_col(&self, ui: &mut eframe::egui::Ui, track: &TrackMetadata, text: &str, settings: &Settings) {
+        let selected_bpm = self.selected_track.borrow().as_ref().and_then(|t| t.bpm.value);
+        let bpm_match = match (selected_bpm, track.bpm.value) {
+            (Some(selected_bpm), Some(bpm)) => crate::track_metadata::compare_bpm(bpm, selected_bpm),
+            _ => None,
+        };
+        let color = bpm_match.and_then(|m| settings.bpm_thresholds.color(m.percent));
+
+        let response = if let Some(color) = color {
+            eframe::egui::Frame::default()
+                .fill(color)
+                .show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    ui.colored_label(eframe::egui::Color32::from_rgb(255, 255, 255), text);
+                })
+                .response
+        } else {
+            ui.label(text)
+        };
+
+        if let Some(bpm_match) = bpm_match {
+            let time = if bpm_match.factor > 1. {
+                " at double time"
+            } else if bpm_match.factor < 1. {
+                " at half time"
+            } else {
+                ""
+            };
+            response.on_hover_text(format!("Pitch this track {:+.2}%{} to match the selected track", bpm_match.percent, time));
         }
     }
 
@@ -314,3 +499,17 @@ impl Playlist {
     }
 }
 
+/**
+ * Entries of a m3u8, XSPF or PLS file, by extension.
+ */
+fn read_segments(path: &std::path::Path) -> Result<Vec<m3u8_rs::MediaSegment>, ImportError> {
+    let text = crate::text_export::read_to_string(path)?;
+    let extension = path.extension().map_or(String::new(), |e| e.to_string_lossy().to_ascii_lowercase());
+    let tracks = match extension.as_str() {
+        "xspf" => crate::xspf::parse(path, &text)?,
+        "pls" => crate::pls::parse(path, &text)?,
+        _ => crate::m3u::parse(path, &text),
+    };
+
+    Ok(tracks.into_iter().map(|t| t.media_segment).collect())
+}
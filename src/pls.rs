@@ -0,0 +1,143 @@
+/*!
+*
+* PLS playlists, the INI style format radio automation tools use.
+*
+*/
+use crate::path_rules::PathProfile;
+use crate::playlist::ImportError;
+use crate::track_metadata::TrackMetadata;
+
+/**
+ * Read the FileN/TitleN/LengthN entries of a PLS file, in entry number order.
+ * Relative paths are resolved against the folder of `path`.
+ */
+pub fn parse(path: &std::path::Path, text: &str) -> Result<Vec<TrackMetadata>, ImportError> {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+    if !lines.next().is_some_and(|l| l.eq_ignore_ascii_case("[playlist]")) {
+        return Err(ImportError::Pls("Missing [playlist] header.".to_string()));
+    }
+
+    // Entry number -> (file, title, length)
+    let mut entries: std::collections::BTreeMap<u32, (String, String, f32)> = Default::default();
+    for line in lines {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        let (field, number) = key.split_at(key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len()));
+        let Ok(number) = number.parse::<u32>() else {
+            continue;
+        };
+
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.0 = value.to_string(),
+            "title" => entry.1 = value.to_string(),
+            "length" => entry.2 = value.parse::<f32>().unwrap_or(-1.),
+            _ => {}
+        }
+    }
+
+    let playlist_dir = path.parent().unwrap_or(std::path::Path::new(""));
+    let tracks = entries
+        .into_values()
+        .filter(|(file, _, _)| !file.is_empty())
+        .enumerate()
+        .map(|(i, (file, display, length))| {
+            let uri = crate::paths::resolve(&file, playlist_dir);
+            // Titles are usually "Artist - Title", fall back to the file name.
+            let (artist, title) = match display.split_once(" - ") {
+                Some((artist, title)) => (artist.trim().to_string(), title.trim().to_string()),
+                None if display.is_empty() => (
+                    String::new(),
+                    std::path::Path::new(&uri)
+                        .file_stem()
+                        .map_or(String::new(), |s| s.to_string_lossy().into_owned()),
+                ),
+                None => (String::new(), display.clone()),
+            };
+            let duration = length.max(0.);
+
+            let mut track = TrackMetadata {
+                number: i as u32 + 1,
+                time: if duration > 0. { crate::track_metadata::format_time(duration) } else { String::new() }.into(),
+                media_segment: m3u8_rs::MediaSegment {
+                    uri,
+                    duration,
+                    title: Some(format!("{} - {}", artist, title)),
+                    ..Default::default()
+                },
+                title,
+                artist,
+                ..Default::default()
+            };
+            track.update_id();
+            track
+        })
+        .collect();
+
+    Ok(tracks)
+}
+
+/**
+ * Write a version 2 PLS playlist. Paths are rewritten with the given
+ * profile for the playlist at `playlist_path`.
+ */
+pub fn write_to<T: std::io::Write>(
+    tracks: &[TrackMetadata],
+    profile: Option<&PathProfile>,
+    playlist_path: &std::path::Path,
+    w: &mut T,
+) -> std::io::Result<()> {
+    writeln!(w, "[playlist]")?;
+
+    for (i, track) in tracks.iter().enumerate() {
+        let n = i + 1;
+        let uri = &track.media_segment.uri;
+        let file = profile.map_or(uri.clone(), |profile| profile.apply(uri, playlist_path));
+        let duration = track.duration_secs().unwrap_or(track.media_segment.duration);
+        let length = if duration > 0. { duration.round() as i64 } else { -1 };
+
+        writeln!(w, "File{}={}", n, file)?;
+        writeln!(w, "Title{}={}", n, crate::m3u::display_name(track).replace(['\r', '\n'], " "))?;
+        writeln!(w, "Length{}={}", n, length)?;
+    }
+
+    writeln!(w, "NumberOfEntries={}", tracks.len())?;
+    writeln!(w, "Version=2")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn reads_entries_in_number_order() {
+        let text = "[playlist]\n\
+                    File2=/music/b.mp3\n\
+                    Title2=Title B\n\
+                    File1=music/a.mp3\n\
+                    Title1=Artist A - Title A\n\
+                    Length1=185\n\
+                    NumberOfEntries=2\n\
+                    Version=2\n";
+        let tracks = parse(std::path::Path::new("/lists/set.pls"), text).unwrap();
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].media_segment.uri, "/lists/music/a.mp3");
+        assert_eq!(tracks[0].artist, "Artist A");
+        assert_eq!(tracks[0].title, "Title A");
+        assert_eq!(tracks[0].time.raw, "03:05");
+        assert_eq!(tracks[1].media_segment.uri, "/music/b.mp3");
+        assert_eq!(tracks[1].artist, "");
+        assert_eq!(tracks[1].title, "Title B");
+    }
+
+    #[test]
+    fn rejects_missing_header() {
+        assert!(parse(std::path::Path::new("set.pls"), "File1=a.mp3").is_err());
+    }
+}
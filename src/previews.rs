@@ -0,0 +1,119 @@
+use crate::playlist::ImportError;
+use crate::track_metadata::TrackMetadata;
+
+// Most waveforms rendered at once, decoding a whole track takes a lot of memory.
+const MAX_WORKERS: usize = 4;
+
+/**
+*
+* Renders waveform previews for tracks that don't have one cached yet,
+* on a small pool of background threads.
+* Poll it every frame until it is finished.
+*
+*/
+pub struct Previews {
+    receiver: std::sync::mpsc::Receiver<ImportError>,
+    cancel: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    finished: bool,
+}
+
+impl Previews {
+    pub fn start(tracks: &[TrackMetadata]) -> Self {
+        let data_dir = std::env::temp_dir().join("playlists");
+        let mut seen = std::collections::HashSet::new();
+        let jobs: std::collections::VecDeque<(String, std::path::PathBuf)> = tracks
+            .iter()
+            .filter(|track| !track.media_segment.uri.is_empty() && seen.insert(track.id))
+            .map(|track| (track.media_segment.uri.clone(), data_dir.join(track.id.to_string() + ".png")))
+            .filter(|(_, png)| !png.exists())
+            .collect();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        if let Err(e) = std::fs::create_dir_all(&data_dir) {
+            let _ = sender.send(ImportError::Preview {
+                path: data_dir.display().to_string(),
+                error: e.to_string(),
+            });
+        } else {
+            let workers = std::thread::available_parallelism()
+                .map_or(1, |n| n.get())
+                .min(MAX_WORKERS)
+                .min(jobs.len());
+            let jobs = std::sync::Arc::new(std::sync::Mutex::new(jobs));
+
+            for _ in 0..workers {
+                let jobs = jobs.clone();
+                let sender = sender.clone();
+                let thread_cancel = cancel.clone();
+                std::thread::spawn(move || loop {
+                    if thread_cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                        return;
+                    }
+
+                    let Some((uri, png)) = jobs.lock().ok().and_then(|mut jobs| jobs.pop_front()) else {
+                        return;
+                    };
+
+                    if let Err(error) = render(&uri, &png) {
+                        let _ = sender.send(ImportError::Preview { path: uri, error });
+                    }
+                });
+            }
+        }
+
+        Self {
+            receiver,
+            cancel,
+            finished: false,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /**
+     * Drain the failures reported since the last call.
+     * The pool is finished once every worker has run out of tracks.
+     */
+    pub fn poll(&mut self) -> Vec<ImportError> {
+        let mut errors = vec![];
+        loop {
+            match self.receiver.try_recv() {
+                Ok(error) => errors.push(error),
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+fn render(uri: &str, png: &std::path::Path) -> Result<(), String> {
+    let dir = png.parent().and_then(|d| d.to_str());
+    let filename = png.file_name().and_then(|f| f.to_str());
+    let (Some(dir), Some(filename)) = (dir, filename) else {
+        return Err(format!("Invalid preview path {}", png.display()));
+    };
+
+    let file = std::fs::File::open(uri).map_err(|e| e.to_string())?;
+    let decoder = rodio::Decoder::new(file).map_err(|e| e.to_string())?;
+
+    audio_visualizer::waveform::png_file::waveform_static_png_visualize(
+        &decoder.collect::<Vec<i16>>(),
+        audio_visualizer::Channels::Mono,
+        dir,
+        filename,
+    );
+    Ok(())
+}
@@ -0,0 +1,270 @@
+use crate::track_metadata::{TrackId, TrackMetadata};
+
+// Maximum difference in seconds for an EXTINF duration to count as the same track.
+const DURATION_TOLERANCE: f32 = 2.;
+// Minimum score for a fuzzy title/artist match to be accepted.
+const FUZZY_THRESHOLD: f32 = 0.6;
+
+/**
+*
+* Result of matching m3u8 segments to csv rows.
+* Holds whatever could not be paired automatically so it can be resolved by hand.
+*
+*/
+#[derive(Default)]
+pub struct Reconciliation {
+    pub unmatched_tracks: Vec<TrackId>,
+    pub unmatched_segments: Vec<m3u8_rs::MediaSegment>,
+    selected_segments: std::collections::HashMap<TrackId, usize>,
+    // Why the last assignment was rejected.
+    error: Option<String>,
+}
+
+impl Reconciliation {
+    pub fn is_empty(&self) -> bool {
+        self.unmatched_tracks.is_empty() && self.unmatched_segments.is_empty()
+    }
+
+    /**
+     * Assign an unmatched segment to an unmatched track by hand.
+     * Files already in the table are rejected, a file can only be in it once.
+     */
+    pub fn assign(&mut self, tracks: &mut [TrackMetadata], track_id: TrackId, segment_index: usize) -> Result<(), String> {
+        let Some(segment) = self.unmatched_segments.get(segment_index) else {
+            return Ok(());
+        };
+        let Some(index) = tracks.iter().position(|t| t.id == track_id) else {
+            return Ok(());
+        };
+
+        let mut track = tracks[index].clone();
+        track.media_segment = segment.clone();
+        track.update_id();
+        if tracks.iter().any(|t| t.id == track.id) {
+            return Err(format!("{} is already in the table.", segment.uri));
+        }
+
+        self.unmatched_segments.remove(segment_index);
+        tracks[index] = track;
+        self.unmatched_tracks.retain(|id| *id != track_id);
+        self.selected_segments.clear();
+        Ok(())
+    }
+
+    /**
+     * Draw the reconciliation report.
+     * Returns false once the user is done with it.
+     */
+    pub fn ui(&mut self, ctx: &eframe::egui::Context, tracks: &mut Vec<TrackMetadata>) -> bool {
+        use eframe::egui;
+        let mut open = true;
+        let mut done = false;
+        let mut assign: Option<(TrackId, usize)> = None;
+        let mut remove: Option<TrackId> = None;
+
+        egui::Window::new("Reconcile import")
+            .open(&mut open)
+            .default_width(600.)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} rows without a file, {} m3u8 entries without a row.",
+                    self.unmatched_tracks.len(),
+                    self.unmatched_segments.len(),
+                ));
+                ui.separator();
+
+                ui.heading("Unmatched rows");
+                egui::ScrollArea::vertical().id_salt("unmatched rows").max_height(200.).show(ui, |ui| {
+                    for track_id in self.unmatched_tracks.iter() {
+                        let Some(track) = tracks.iter().find(|t| t.id == *track_id) else {
+                            continue;
+                        };
+
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{}. {} - {} ({})", track.number, track.artist, track.title, track.time));
+
+                            let selected = self.selected_segments.entry(*track_id).or_insert(0);
+                            egui::ComboBox::from_id_salt(("segment", *track_id))
+                                .width(250.)
+                                .selected_text(self.unmatched_segments.get(*selected).map_or(String::new(), segment_label))
+                                .show_ui(ui, |ui| {
+                                    for (i, segment) in self.unmatched_segments.iter().enumerate() {
+                                        ui.selectable_value(selected, i, segment_label(segment));
+                                    }
+                                });
+
+                            if ui.add_enabled(!self.unmatched_segments.is_empty(), egui::Button::new("Assign")).clicked() {
+                                assign = Some((*track_id, *selected));
+                            }
+                            if ui.button("Remove row").clicked() {
+                                remove = Some(*track_id);
+                            }
+                        });
+                    }
+                });
+
+                ui.separator();
+                ui.heading("Unmatched m3u8 entries");
+                egui::ScrollArea::vertical().id_salt("unmatched segments").max_height(200.).show(ui, |ui| {
+                    for segment in self.unmatched_segments.iter() {
+                        ui.label(segment_label(segment));
+                    }
+                });
+
+                ui.separator();
+                if let Some(error) = &self.error {
+                    ui.colored_label(egui::Color32::from_rgb(220, 60, 60), error);
+                }
+                done = ui.button("Done").clicked();
+            });
+
+        if let Some((track_id, segment_index)) = assign {
+            self.error = self.assign(tracks, track_id, segment_index).err();
+        }
+
+        if let Some(track_id) = remove {
+            tracks.retain(|t| t.id != track_id);
+            self.unmatched_tracks.retain(|id| *id != track_id);
+        }
+
+        open && !done
+    }
+}
+
+fn segment_label(segment: &m3u8_rs::MediaSegment) -> String {
+    format!(
+        "{} ({}:{:02})",
+        segment.title.as_deref().unwrap_or(segment.uri.as_str()),
+        segment.duration as u32 / 60,
+        segment.duration as u32 % 60,
+    )
+}
+
+/**
+ * Pair m3u8 segments with csv rows by EXTINF title/artist and duration.
+ * Exact matches are taken first, remaining pairs are matched fuzzily.
+ */
+pub fn match_segments(tracks: &mut [TrackMetadata], segments: Vec<m3u8_rs::MediaSegment>) -> Reconciliation {
+    let mut track_matched = vec![false; tracks.len()];
+    let mut segment_matched = vec![false; segments.len()];
+
+    // Exact pass, in file order so duplicate entries pair up in sequence.
+    for (s, segment) in segments.iter().enumerate() {
+        let segment_name = normalize(segment_name(segment));
+        let found = tracks.iter().enumerate().position(|(t, track)| {
+            !track_matched[t]
+                && (normalize(&format!("{} - {}", track.artist, track.title)) == segment_name
+                    || normalize(&track.title) == segment_name)
+                && durations_agree(track, segment)
+        });
+
+        if let Some(t) = found {
+            track_matched[t] = true;
+            segment_matched[s] = true;
+            tracks[t].media_segment = segment.clone();
+        }
+    }
+
+    // Fuzzy pass, best scoring pairs first.
+    let mut candidates = vec![];
+    for (s, segment) in segments.iter().enumerate().filter(|(s, _)| !segment_matched[*s]) {
+        for (t, track) in tracks.iter().enumerate().filter(|(t, _)| !track_matched[*t]) {
+            let score = fuzzy_score(track, segment);
+            if score >= FUZZY_THRESHOLD {
+                candidates.push((score, t, s));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    for (_, t, s) in candidates {
+        if track_matched[t] || segment_matched[s] {
+            continue;
+        }
+        track_matched[t] = true;
+        segment_matched[s] = true;
+        tracks[t].media_segment = segments[s].clone();
+    }
+
+    for (track, matched) in tracks.iter_mut().zip(track_matched.iter()) {
+        if *matched {
+            track.update_id();
+        }
+    }
+
+    Reconciliation {
+        unmatched_tracks: tracks
+            .iter()
+            .zip(track_matched.iter())
+            .filter(|(_, matched)| !**matched)
+            .map(|(track, _)| track.id)
+            .collect(),
+        unmatched_segments: segments
+            .into_iter()
+            .zip(segment_matched.iter())
+            .filter(|(_, matched)| !**matched)
+            .map(|(segment, _)| segment)
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/**
+ * EXTINF display name, falling back to the file name without extension.
+ */
+fn segment_name(segment: &m3u8_rs::MediaSegment) -> &str {
+    if let Some(title) = segment.title.as_deref().filter(|t| !t.trim().is_empty()) {
+        return title;
+    }
+
+    let file_name = segment.uri.rsplit(['/', '\\']).next().unwrap_or(&segment.uri);
+    file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem)
+}
+
+fn durations_agree(track: &TrackMetadata, segment: &m3u8_rs::MediaSegment) -> bool {
+    match track.duration_secs() {
+        Some(duration) if segment.duration > 0. => (duration - segment.duration).abs() <= DURATION_TOLERANCE,
+        _ => true,
+    }
+}
+
+/**
+ * Token overlap of artist/title against the EXTINF name, scaled by how close the durations are.
+ */
+fn fuzzy_score(track: &TrackMetadata, segment: &m3u8_rs::MediaSegment) -> f32 {
+    let track_name = normalize(&format!("{} {}", track.artist, track.title));
+    let segment_name = normalize(segment_name(segment));
+    let track_tokens: std::collections::HashSet<&str> = track_name.split(' ').filter(|t| !t.is_empty()).collect();
+    let segment_tokens: std::collections::HashSet<&str> = segment_name.split(' ').filter(|t| !t.is_empty()).collect();
+
+    if track_tokens.is_empty() || segment_tokens.is_empty() {
+        return 0.;
+    }
+
+    let common = track_tokens.intersection(&segment_tokens).count() as f32;
+    let similarity = 2. * common / (track_tokens.len() + segment_tokens.len()) as f32;
+
+    let duration_factor = match track.duration_secs() {
+        Some(duration) if segment.duration > 0. => {
+            let diff = (duration - segment.duration).abs();
+            if diff <= DURATION_TOLERANCE {
+                1.
+            } else {
+                (1. - diff / 30.).max(0.)
+            }
+        }
+        _ => 0.8,
+    };
+
+    similarity * duration_factor
+}
+
+fn normalize(s: &str) -> String {
+    s.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
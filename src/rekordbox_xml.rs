@@ -0,0 +1,244 @@
+use crate::collection::{Collection, CollectionPlaylist};
+use crate::playlist::ImportError;
+use crate::track_metadata::{TrackId, TrackMetadata};
+
+/**
+ * Ask for a rekordbox.xml file and parse it.
+ */
+pub fn pick() -> Option<Result<Collection, ImportError>> {
+    rfd::FileDialog::new()
+        .add_filter("Rekordbox XML", &["xml"])
+        .set_directory("/")
+        .pick_file()
+        .map(|path| {
+            let text = std::fs::read_to_string(&path)?;
+            parse(path, &text)
+        })
+}
+
+/**
+ * Parse a Rekordbox collection export (DJ_PLAYLISTS).
+ * Tracks are keyed by TrackID, playlists list their TRACK Key attributes.
+ */
+pub fn parse(path: std::path::PathBuf, text: &str) -> Result<Collection, ImportError> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| ImportError::Xml(e.to_string()))?;
+    let root = doc.root_element();
+    if !root.has_tag_name("DJ_PLAYLISTS") {
+        return Err(ImportError::Xml("Not a Rekordbox collection, missing DJ_PLAYLISTS.".to_string()));
+    }
+
+    let mut tracks = std::collections::HashMap::new();
+    // Playlists may reference tracks by location instead of id.
+    let mut locations = std::collections::HashMap::new();
+
+    let collection = root.children().find(|n| n.has_tag_name("COLLECTION"));
+    for (i, node) in collection
+        .iter()
+        .flat_map(|c| c.children())
+        .filter(|n| n.has_tag_name("TRACK"))
+        .enumerate()
+    {
+        let attr = |name: &str| node.attribute(name).unwrap_or_default().to_string();
+        let Some(track_id) = node.attribute("TrackID") else {
+            continue;
+        };
+
+        let duration = attr("TotalTime").parse::<f32>().unwrap_or(0.);
+        let location = attr("Location");
+        let track = TrackMetadata {
+            number: i as u32 + 1,
+            title: attr("Name"),
+            artist: attr("Artist"),
+            album: attr("Album"),
+            bpm: attr("AverageBpm").into(),
+            key: attr("Tonality").into(),
+            time: crate::track_metadata::format_time(duration).into(),
+            message: attr("Comments"),
+            date_added: attr("DateAdded").into(),
+            genre: attr("Genre"),
+            media_segment: m3u8_rs::MediaSegment {
+                uri: crate::paths::uri_to_path(&location),
+                duration,
+                title: Some(format!("{} - {}", attr("Artist"), attr("Name"))),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        locations.insert(location, track_id.to_string());
+        tracks.insert(track_id.to_string(), track);
+    }
+
+    let mut playlists = vec![];
+    if let Some(root_node) = root
+        .children()
+        .find(|n| n.has_tag_name("PLAYLISTS"))
+        .and_then(|p| p.children().find(|n| n.has_tag_name("NODE")))
+    {
+        collect_playlists(root_node, "", &locations, &mut playlists);
+    }
+
+    Ok(Collection::new(path, tracks, playlists))
+}
+
+/**
+ * Walk the NODE tree. Type 0 is a folder, Type 1 a playlist.
+ */
+fn collect_playlists(
+    node: roxmltree::Node,
+    parent: &str,
+    locations: &std::collections::HashMap<String, String>,
+    playlists: &mut Vec<CollectionPlaylist>,
+) {
+    for child in node.children().filter(|n| n.has_tag_name("NODE")) {
+        let name = child.attribute("Name").unwrap_or_default();
+        let full_name = if parent.is_empty() {
+            name.to_string()
+        } else {
+            format!("{} / {}", parent, name)
+        };
+
+        match child.attribute("Type") {
+            Some("0") => collect_playlists(child, &full_name, locations, playlists),
+            Some("1") => {
+                // KeyType 1 means TRACK Key holds the Location instead of the TrackID.
+                let by_location = child.attribute("KeyType") == Some("1");
+                let track_keys = child
+                    .children()
+                    .filter(|n| n.has_tag_name("TRACK"))
+                    .filter_map(|n| n.attribute("Key"))
+                    .filter_map(|key| {
+                        if by_location {
+                            locations.get(key).cloned()
+                        } else {
+                            Some(key.to_string())
+                        }
+                    })
+                    .collect();
+
+                playlists.push(CollectionPlaylist {
+                    name: full_name,
+                    track_keys,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/**
+ * Ask for a file and write the tracks to it as a rekordbox.xml playlist.
+ */
+pub fn export(tracks: &[TrackMetadata]) -> std::io::Result<()> {
+    let file = rfd::FileDialog::new()
+        .add_filter("Rekordbox XML", &["xml"])
+        .set_directory("/")
+        .save_file();
+
+    let Some(file) = file else {
+        return Ok(());
+    };
+
+    let name = file.file_stem().map_or("Playlist".to_string(), |n| n.to_string_lossy().into_owned());
+    let mut f = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(file)?;
+    write_to(tracks, &name, &mut f)
+}
+
+/**
+ * Key the way Rekordbox writes Tonality, whatever the display notation:
+ * musical with flats, except F# and F#m. Keys that didn't parse keep their text.
+ */
+fn tonality(track: &TrackMetadata) -> String {
+    match track.key.value {
+        Some(key) if key.pitch() == 6 => if key.minor { "F#m" } else { "F#" }.to_string(),
+        Some(key) => key.to_musical(true),
+        None => track.key.raw.clone(),
+    }
+}
+
+/**
+ * Write a DJ_PLAYLISTS document with the tracks in the COLLECTION
+ * and a single playlist under the ROOT node keeping their order.
+ * Rekordbox shows it under "Imported Library".
+ */
+pub fn write_to<T: std::io::Write>(tracks: &[TrackMetadata], name: &str, w: &mut T) -> std::io::Result<()> {
+    use crate::xml::escape;
+
+    // The same file may appear more than once in a set, list it once in the collection.
+    let mut track_ids: Vec<TrackId> = vec![];
+    for track in tracks {
+        if !track_ids.contains(&track.id) {
+            track_ids.push(track.id);
+        }
+    }
+
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, r#"<DJ_PLAYLISTS Version="1.0.0">"#)?;
+    writeln!(w, r#"  <PRODUCT Name="playlists" Version="{}" Company=""/>"#, env!("CARGO_PKG_VERSION"))?;
+    writeln!(w, r#"  <COLLECTION Entries="{}">"#, track_ids.len())?;
+
+    for (i, id) in track_ids.iter().enumerate() {
+        let Some(track) = tracks.iter().find(|t| t.id == *id) else {
+            continue;
+        };
+
+        let bpm = track.bpm.value.map_or(String::new(), |bpm| format!("{:.2}", bpm.0));
+        writeln!(
+            w,
+            r#"    <TRACK TrackID="{}" Name="{}" Artist="{}" Album="{}" Genre="{}" TotalTime="{}" DateAdded="{}" AverageBpm="{}" Tonality="{}" Comments="{}" Location="{}"/>"#,
+            i + 1,
+            escape(&track.title),
+            escape(&track.artist),
+            escape(&track.album),
+            escape(&track.genre),
+            track.media_segment.duration.round() as u32,
+            escape(&track.date_added.raw),
+            bpm,
+            escape(&tonality(track)),
+            escape(&track.message),
+            escape(&crate::paths::path_to_uri(&track.media_segment.uri)),
+        )?;
+    }
+
+    writeln!(w, "  </COLLECTION>")?;
+    writeln!(w, "  <PLAYLISTS>")?;
+    writeln!(w, r#"    <NODE Type="0" Name="ROOT" Count="1">"#)?;
+    writeln!(w, r#"      <NODE Name="{}" Type="1" KeyType="0" Entries="{}">"#, escape(name), tracks.len())?;
+
+    for track in tracks {
+        if let Some(i) = track_ids.iter().position(|id| *id == track.id) {
+            writeln!(w, r#"        <TRACK Key="{}"/>"#, i + 1)?;
+        }
+    }
+
+    writeln!(w, "      </NODE>")?;
+    writeln!(w, "    </NODE>")?;
+    writeln!(w, "  </PLAYLISTS>")?;
+    writeln!(w, "</DJ_PLAYLISTS>")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tonality_is_musical() {
+        let with_key = |key: &str| TrackMetadata {
+            key: key.to_string().into(),
+            ..Default::default()
+        };
+
+        assert_eq!(tonality(&with_key("8A")), "Am");
+        assert_eq!(tonality(&with_key("1A")), "Abm");
+        assert_eq!(tonality(&with_key("11A")), "F#m");
+        assert_eq!(tonality(&with_key("2B")), "F#");
+        assert_eq!(tonality(&with_key("3B")), "Db");
+        assert_eq!(tonality(&with_key("unknown")), "unknown");
+    }
+}
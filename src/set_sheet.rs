@@ -0,0 +1,185 @@
+/*!
+*
+* Human readable set sheet of the export table, as standalone HTML or Markdown.
+*
+*/
+use crate::harmonic_rules::RuleSet;
+use crate::settings::Settings;
+use crate::track_metadata::{compare_keys, format_time, KeyNotation, TrackMetadata};
+
+/**
+ * One line of the sheet, with everything already formatted.
+ */
+struct SheetRow {
+    position: usize,
+    start: String,
+    title: String,
+    artist: String,
+    bpm: String,
+    key: String,
+    compatibility: String,
+    color: Option<eframe::egui::Color32>,
+    notes: String,
+}
+
+fn rows(tracks: &[TrackMetadata], notation: KeyNotation, rules: Option<&RuleSet>) -> Vec<SheetRow> {
+    let mut start = 0.;
+    let mut previous: Option<&TrackMetadata> = None;
+    let mut rows = vec![];
+
+    for (i, track) in tracks.iter().enumerate() {
+        // Name of the rule that allows the transition from the previous track.
+        let rule = match (previous.and_then(|p| p.key.value), track.key.value, rules) {
+            (Some(a), Some(b), Some(rules)) => compare_keys(&a, &b, rules),
+            _ => None,
+        };
+        let compatibility = match (rule, previous) {
+            (Some(rule), _) => rule.name.clone(),
+            (None, Some(previous)) if previous.key.value.is_some() && track.key.value.is_some() => "No match".to_string(),
+            _ => String::new(),
+        };
+        let color = rule.and_then(|rule| rule.color());
+
+        let notes = [track.message.as_str(), track.my_tag.as_str()]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" / ");
+
+        rows.push(SheetRow {
+            position: i + 1,
+            start: format_time(start),
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            bpm: track.bpm.to_string(),
+            key: track.key_text(notation),
+            compatibility,
+            color,
+            notes,
+        });
+
+        start += track.duration_secs().unwrap_or(track.media_segment.duration);
+        previous = Some(track);
+    }
+
+    rows
+}
+
+const HEADERS: [&str; 8] = ["#", "Start", "Title", "Artist", "BPM", "Key", "Transition", "Notes"];
+
+/**
+ * Ask for a file name and write the sheet, as Markdown for .md files and HTML otherwise.
+ */
+pub fn export(tracks: &[TrackMetadata], settings: &Settings) -> std::io::Result<()> {
+    let file = rfd::FileDialog::new()
+        .add_filter("HTML", &["html", "htm"])
+        .add_filter("Markdown", &["md"])
+        .set_directory("/")
+        .save_file();
+
+    let Some(file) = file else {
+        return Ok(());
+    };
+
+    let title = file.file_stem().map_or("Set".to_string(), |n| n.to_string_lossy().into_owned());
+    let markdown = file.extension().is_some_and(|e| e.eq_ignore_ascii_case("md"));
+    let mut f = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(file)?;
+
+    if markdown {
+        write_markdown(tracks, &title, settings.key_notation, settings.rules(), &mut f)
+    } else {
+        write_html(tracks, &title, settings.key_notation, settings.rules(), &mut f)
+    }
+}
+
+pub fn write_html<T: std::io::Write>(
+    tracks: &[TrackMetadata],
+    title: &str,
+    notation: KeyNotation,
+    rules: Option<&RuleSet>,
+    w: &mut T,
+) -> std::io::Result<()> {
+    use crate::xml::escape;
+
+    writeln!(w, "<!DOCTYPE html>")?;
+    writeln!(w, "<html>")?;
+    writeln!(w, "<head>")?;
+    writeln!(w, "<meta charset=\"utf-8\">")?;
+    writeln!(w, "<title>{}</title>", escape(title))?;
+    writeln!(
+        w,
+        "<style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} \
+         th, td {{ border: 1px solid #999; padding: 4px 8px; text-align: left; }}</style>"
+    )?;
+    writeln!(w, "</head>")?;
+    writeln!(w, "<body>")?;
+    writeln!(w, "<h1>{}</h1>", escape(title))?;
+    writeln!(w, "<table>")?;
+
+    write!(w, "<tr>")?;
+    for header in HEADERS {
+        write!(w, "<th>{}</th>", header)?;
+    }
+    writeln!(w, "</tr>")?;
+
+    for row in rows(tracks, notation, rules) {
+        let style = row.color.map_or(String::new(), |c| {
+            format!(" style=\"background-color: #{:02x}{:02x}{:02x}; color: white\"", c.r(), c.g(), c.b())
+        });
+        writeln!(
+            w,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td{}>{}</td><td>{}</td></tr>",
+            row.position,
+            row.start,
+            escape(&row.title),
+            escape(&row.artist),
+            escape(&row.bpm),
+            escape(&row.key),
+            style,
+            escape(&row.compatibility),
+            escape(&row.notes),
+        )?;
+    }
+
+    writeln!(w, "</table>")?;
+    writeln!(w, "</body>")?;
+    writeln!(w, "</html>")?;
+    Ok(())
+}
+
+pub fn write_markdown<T: std::io::Write>(
+    tracks: &[TrackMetadata],
+    title: &str,
+    notation: KeyNotation,
+    rules: Option<&RuleSet>,
+    w: &mut T,
+) -> std::io::Result<()> {
+    // Pipes would end the cell early.
+    let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+
+    writeln!(w, "# {}", title)?;
+    writeln!(w)?;
+    writeln!(w, "| {} |", HEADERS.join(" | "))?;
+    writeln!(w, "|{}", "---|".repeat(HEADERS.len()))?;
+
+    for row in rows(tracks, notation, rules) {
+        writeln!(
+            w,
+            "| {} | {} | {} | {} | {} | {} | {} | {} |",
+            row.position,
+            row.start,
+            cell(&row.title),
+            cell(&row.artist),
+            cell(&row.bpm),
+            cell(&row.key),
+            cell(&row.compatibility),
+            cell(&row.notes),
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use crate::harmonic_rules::RuleSet;
+use crate::track_metadata::{KeyNotation, TrackMetadata};
+
+const SETTINGS_CONFIG: &str = "settings";
+
+/**
+ * Tempo every track is assumed to be played at when comparing keys.
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum TargetBpm {
+    #[default]
+    Off,
+    Fixed(f32),
+    SelectedTrack,
+}
+
+/**
+ * Rows whose tempo is within `max_percent` of the selected track get `color`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BpmThreshold {
+    pub max_percent: f32,
+    pub color: [u8; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BpmThresholds(pub Vec<BpmThreshold>);
+
+impl Default for BpmThresholds {
+    fn default() -> Self {
+        Self(vec![
+            BpmThreshold {
+                max_percent: 3.,
+                color: [19, 128, 11],
+            },
+            BpmThreshold {
+                max_percent: 6.,
+                color: [143, 119, 11],
+            },
+            BpmThreshold {
+                max_percent: 8.,
+                color: [160, 80, 20],
+            },
+        ])
+    }
+}
+
+impl BpmThresholds {
+    /**
+     * Color of the tightest threshold the pitch fits in.
+     */
+    pub fn color(&self, percent: f32) -> Option<eframe::egui::Color32> {
+        self.0
+            .iter()
+            .filter(|t| percent.abs() <= t.max_percent)
+            .min_by(|a, b| a.max_percent.total_cmp(&b.max_percent))
+            .map(|t| eframe::egui::Color32::from_rgb(t.color[0], t.color[1], t.color[2]))
+    }
+}
+
+/**
+*
+* Display and export preferences, saved whenever they change.
+*
+*/
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub key_notation: KeyNotation,
+    // Name of the harmonic rule set used to color keys.
+    pub rule_set: String,
+    #[serde(skip)]
+    pub rule_sets: Vec<RuleSet>,
+    pub target_bpm: TargetBpm,
+    pub bpm_thresholds: BpmThresholds,
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let mut settings: Self = crate::config::load(SETTINGS_CONFIG);
+        settings.rule_sets = crate::harmonic_rules::load_rule_sets();
+        settings
+    }
+
+    /**
+     * The selected rule set, or the first one when it no longer exists.
+     */
+    pub fn rules(&self) -> Option<&RuleSet> {
+        self.rule_sets
+            .iter()
+            .find(|s| s.name == self.rule_set)
+            .or(self.rule_sets.first())
+    }
+
+    /**
+     * Target tempo, if any, given the selected track.
+     */
+    pub fn target_bpm(&self, selected: Option<&TrackMetadata>) -> Option<f32> {
+        match self.target_bpm {
+            TargetBpm::Off => None,
+            TargetBpm::Fixed(bpm) => Some(bpm),
+            TargetBpm::SelectedTrack => selected.and_then(|t| t.bpm.value).map(|bpm| bpm.0),
+        }
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = crate::config::save(SETTINGS_CONFIG, self) {
+            crate::config::report(format!("Could not save settings: {}", e));
+        }
+    }
+
+    /**
+     * Settings controls for the settings panel.
+     */
+    pub fn ui(&mut self, ui: &mut eframe::egui::Ui) {
+        let before = (self.key_notation, self.rule_set.clone(), self.target_bpm, self.bpm_thresholds.clone());
+        ui.horizontal(|ui| {
+            ui.label("Keys:");
+            eframe::egui::ComboBox::from_id_salt("key notation")
+                .selected_text(self.key_notation.label())
+                .show_ui(ui, |ui| {
+                    for notation in KeyNotation::ALL {
+                        ui.selectable_value(&mut self.key_notation, notation, notation.label());
+                    }
+                });
+        });
+
+        let current = self.rules().map_or(String::new(), |s| s.name.clone());
+        ui.horizontal(|ui| {
+            ui.label("Mixing rules:");
+            eframe::egui::ComboBox::from_id_salt("harmonic rules")
+                .selected_text(&current)
+                .show_ui(ui, |ui| {
+                    for set in self.rule_sets.iter() {
+                        ui.selectable_value(&mut self.rule_set, set.name.clone(), &set.name);
+                    }
+                });
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Target BPM:");
+            eframe::egui::ComboBox::from_id_salt("target bpm")
+                .selected_text(match self.target_bpm {
+                    TargetBpm::Off => "Off",
+                    TargetBpm::Fixed(_) => "Fixed",
+                    TargetBpm::SelectedTrack => "Selected track",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.target_bpm, TargetBpm::Off, "Off");
+                    if ui
+                        .selectable_label(matches!(self.target_bpm, TargetBpm::Fixed(_)), "Fixed")
+                        .clicked()
+                        && !matches!(self.target_bpm, TargetBpm::Fixed(_))
+                    {
+                        self.target_bpm = TargetBpm::Fixed(128.);
+                    }
+                    ui.selectable_value(&mut self.target_bpm, TargetBpm::SelectedTrack, "Selected track");
+                });
+            if let TargetBpm::Fixed(bpm) = &mut self.target_bpm {
+                ui.add(eframe::egui::DragValue::new(bpm).range(40.0..=250.0).speed(0.1));
+            }
+        });
+
+        ui.menu_button("BPM colors", |ui| {
+            let mut remove = None;
+            for (i, threshold) in self.bpm_thresholds.0.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Up to");
+                        ui.add(
+                            eframe::egui::DragValue::new(&mut threshold.max_percent)
+                                .range(0.0..=50.0)
+                                .speed(0.1)
+                                .suffix("%"),
+                        );
+                        ui.color_edit_button_srgb(&mut threshold.color);
+                        if ui.button("✖").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                });
+            }
+            if let Some(i) = remove {
+                self.bpm_thresholds.0.remove(i);
+            }
+
+            if ui.button("Add color").clicked() {
+                let max_percent = self.bpm_thresholds.0.iter().map(|t| t.max_percent).fold(0., f32::max) + 2.;
+                self.bpm_thresholds.0.push(BpmThreshold {
+                    max_percent: max_percent.min(50.),
+                    color: [160, 80, 20],
+                });
+            }
+        });
+
+        if before != (self.key_notation, self.rule_set.clone(), self.target_bpm, self.bpm_thresholds.clone()) {
+            self.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::Color32;
+
+    #[test]
+    fn tightest_threshold_wins() {
+        let thresholds = BpmThresholds::default();
+        assert_eq!(thresholds.color(2.5), Some(Color32::from_rgb(19, 128, 11)));
+        assert_eq!(thresholds.color(-4.), Some(Color32::from_rgb(143, 119, 11)));
+        assert_eq!(thresholds.color(8.), Some(Color32::from_rgb(160, 80, 20)));
+        assert_eq!(thresholds.color(9.), None);
+        assert_eq!(BpmThresholds(vec![]).color(0.), None);
+    }
+}
@@ -0,0 +1,183 @@
+/*!
+*
+* Helpers for reading and writing Rekordbox "text" playlist exports.
+* Rekordbox writes UTF-16LE with a BOM, other tools write UTF-8 with
+* tabs, commas or semicolons, so both encoding and delimiter are sniffed.
+*
+*/
+
+/**
+ * Read a file and decode it according to its BOM.
+ * Files without a BOM are checked for UTF-16 by looking for zero bytes.
+ */
+pub fn read_to_string(path: &std::path::Path) -> std::io::Result<String> {
+    let bytes = std::fs::read(path)?;
+    Ok(decode(&bytes))
+}
+
+pub fn decode(bytes: &[u8]) -> String {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        [0xFF, 0xFE, rest @ ..] => decode_utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => decode_utf16(rest, u16::from_be_bytes),
+        _ => {
+            // Without a BOM, ASCII text in UTF-16 has every other byte zeroed.
+            let sample = &bytes[..bytes.len().min(512)];
+            let zeros_even = sample.iter().step_by(2).filter(|b| **b == 0).count();
+            let zeros_odd = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+
+            if zeros_odd > sample.len() / 4 {
+                decode_utf16(bytes, u16::from_le_bytes)
+            } else if zeros_even > sample.len() / 4 {
+                decode_utf16(bytes, u16::from_be_bytes)
+            } else {
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect();
+
+    String::from_utf16_lossy(&units)
+}
+
+/**
+ * Pick the delimiter that splits the header line into the most columns.
+ * Defaults to tab which is what Rekordbox uses.
+ */
+pub fn detect_delimiter(text: &str) -> u8 {
+    let header = text.lines().next().unwrap_or_default();
+
+    [b'\t', b',', b';']
+        .into_iter()
+        .map(|d| (d, header.bytes().filter(|b| *b == d).count()))
+        .filter(|(_, count)| *count > 0)
+        .max_by_key(|(_, count)| *count)
+        .map_or(b'\t', |(d, _)| d)
+}
+
+/**
+ * Ask for a file and write the tracks to it as a tab separated table.
+ */
+pub fn export(tracks: &[crate::track_metadata::TrackMetadata]) -> std::io::Result<()> {
+    let file = rfd::FileDialog::new()
+        .add_filter("tsv", &["txt", "tsv"])
+        .set_directory("/")
+        .save_file();
+
+    match file {
+        Some(file) => Ok(write_to(tracks, &file)?),
+        None => Ok(()),
+    }
+}
+
+/**
+ * Write every column TrackMetadata deserializes from, plus the resolved
+ * file path and exact length, so the file imports back into the same table.
+ * Values are written as imported, whatever the display notation.
+ */
+pub fn write_to(tracks: &[crate::track_metadata::TrackMetadata], path: &std::path::Path) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::default()
+        .delimiter(b'\t')
+        .from_path(path)?;
+
+    writer.write_record(crate::column_mapping::FIELDS)?;
+    for track in tracks {
+        let uri = std::path::Path::new(&track.media_segment.uri);
+        let location = std::fs::canonicalize(uri).unwrap_or_else(|_| uri.to_path_buf());
+
+        // Same order as column_mapping::FIELDS.
+        writer.write_record([
+            track.number.to_string(),
+            track.title.clone(),
+            track.artist.clone(),
+            track.album.clone(),
+            track.bpm.raw.clone(),
+            track.key.raw.clone(),
+            track.time.raw.clone(),
+            track.message.clone(),
+            track.date_added.raw.clone(),
+            track.genre.clone(),
+            track.my_tag.clone(),
+            location.to_string_lossy().into_owned(),
+            if track.media_segment.duration > 0. { track.media_segment.duration.to_string() } else { String::new() },
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::track_metadata::TrackMetadata;
+
+    #[test]
+    fn tsv_round_trip_keeps_raw_values() {
+        let mut track = TrackMetadata {
+            number: 1,
+            title: "Title, with comma\tand tab".to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            bpm: "127,98".to_string().into(),
+            key: "Abm".to_string().into(),
+            time: "05:12".to_string().into(),
+            message: "Message".to_string(),
+            date_added: "2024-01-31".to_string().into(),
+            genre: "Techno".to_string(),
+            my_tag: "Peak".to_string(),
+            ..Default::default()
+        };
+        track.media_segment.uri = "/music/does not exist.mp3".to_string();
+        track.media_segment.duration = 312.456;
+        track.update_id();
+
+        let path = std::env::temp_dir().join(format!("playlists-round-trip-{}.tsv", std::process::id()));
+        write_to(std::slice::from_ref(&track), &path).unwrap();
+        let preview = crate::column_mapping::ImportPreview::open(path.clone()).unwrap();
+        let (tracks, warnings) = preview.tracks(None);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(warnings.is_empty());
+        assert_eq!(tracks.len(), 1);
+        let read = &tracks[0];
+        assert_eq!(read.id, track.id);
+        assert_eq!(read.title, track.title);
+        assert_eq!(read.artist, track.artist);
+        assert_eq!(read.album, track.album);
+        assert_eq!(read.bpm.raw, "127,98");
+        assert_eq!(read.key.raw, "Abm");
+        assert_eq!(read.time.raw, "05:12");
+        assert_eq!(read.date_added.raw, "2024-01-31");
+        assert_eq!(read.genre, track.genre);
+        assert_eq!(read.my_tag, track.my_tag);
+        assert_eq!(read.media_segment.uri, track.media_segment.uri);
+        assert_eq!(read.media_segment.duration, 312.456);
+    }
+
+    #[test]
+    fn detects_delimiter() {
+        assert_eq!(detect_delimiter("#\tTrack Title\tArtist"), b'\t');
+        assert_eq!(detect_delimiter("#;Track Title;Artist"), b';');
+        assert_eq!(detect_delimiter("#,Track Title,Artist"), b',');
+    }
+
+    #[test]
+    fn decodes_utf16() {
+        let text = "#\tTrack Title\n1\tCafé";
+
+        let mut utf16_le = vec![0xFF, 0xFE];
+        utf16_le.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode(&utf16_le), text);
+
+        let mut utf8_bom = vec![0xEF, 0xBB, 0xBF];
+        utf8_bom.extend(text.as_bytes());
+        assert_eq!(decode(&utf8_bom), text);
+    }
+}
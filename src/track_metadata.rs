@@ -1,44 +1,451 @@
-#[derive(Debug, serde::Deserialize, Clone)]
+/**
+* A column value parsed at import, keeping the original text for display.
+* Empty text is "missing", text that doesn't parse is "invalid".
+*/
+#[derive(Debug, Clone)]
+pub struct Parsed<T> {
+    pub raw: String,
+    pub value: Option<T>,
+}
+
+impl<T> Default for Parsed<T> {
+    fn default() -> Self {
+        Self {
+            raw: String::new(),
+            value: None,
+        }
+    }
+}
+
+impl<T: std::str::FromStr> From<String> for Parsed<T> {
+    fn from(raw: String) -> Self {
+        let value = raw.trim().parse().ok();
+        Self { raw, value }
+    }
+}
+
+impl<T> Parsed<T> {
+    pub fn is_invalid(&self) -> bool {
+        self.value.is_none() && !self.raw.trim().is_empty()
+    }
+}
+
+impl<T> std::fmt::Display for Parsed<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+impl<'de, T: std::str::FromStr> serde::Deserialize<'de> for Parsed<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Bpm(pub f32);
+
+impl std::str::FromStr for Bpm {
+    type Err = &'static str;
+
+    // Some locales export "128,00".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.replace(',', ".")
+            .parse::<f32>()
+            .ok()
+            .filter(|bpm| bpm.is_finite() && *bpm > 0.)
+            .map(Bpm)
+            .ok_or("Invalid bpm")
+    }
+}
+
+/**
+* Track length from "mm:ss", "h:mm:ss" or plain seconds.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct TrackTime(pub std::time::Duration);
+
+impl std::str::FromStr for TrackTime {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() > 3 {
+            return Err("Invalid time");
+        }
+
+        let secs = parts.iter().try_fold(0., |acc, part| {
+            part.parse::<f64>()
+                .ok()
+                .filter(|v| *v >= 0.)
+                .map(|v| acc * 60. + v)
+        });
+
+        secs.map(|secs| TrackTime(std::time::Duration::from_secs_f64(secs)))
+            .ok_or("Invalid time")
+    }
+}
+
+/**
+* A key on the camelot wheel, 1-12 with A for minor and B for major.
+* Parses camelot ("8A"), Open Key ("1m", "1d") and musical notation
+* ("Am", "F#m", "Abm", "A minor").
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CamelotKey {
+    pub number: u8,
+    pub minor: bool,
+}
+
+const SHARP_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+const FLAT_NAMES: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+
+impl CamelotKey {
+    // Am is 8A and C is 8B, a fifth up (7 semitones) is one step clockwise.
+    fn from_pitch(pitch: i32, minor: bool) -> Self {
+        let reference = if minor { 9 } else { 0 };
+        let steps = ((pitch - reference) * 7).rem_euclid(12);
+        Self {
+            number: ((7 + steps) % 12 + 1) as u8,
+            minor,
+        }
+    }
+
+    /**
+     * Pitch class of the root note, 0 is C.
+     */
+    pub fn pitch(&self) -> u8 {
+        let reference = if self.minor { 9 } else { 0 };
+        // 7 is its own inverse mod 12, so this undoes from_pitch.
+        (reference + (self.number as i32 - 8) * 7).rem_euclid(12) as u8
+    }
+
+    /**
+     * Steps between the two numbers around the wheel, 0 to 6.
+     */
+    pub fn distance(&self, other: &Self) -> u8 {
+        let d = (self.number as i32 - other.number as i32).rem_euclid(12) as u8;
+        d.min(12 - d)
+    }
+
+    /**
+     * The key played `semitones` higher (or lower when negative).
+     */
+    pub fn transpose(self, semitones: i32) -> Self {
+        Self::from_pitch(self.pitch() as i32 + semitones, self.minor)
+    }
+
+    pub fn to_open_key(self) -> String {
+        format!("{}{}", (self.number + 4) % 12 + 1, if self.minor { "m" } else { "d" })
+    }
+
+    pub fn to_musical(self, flats: bool) -> String {
+        let names = if flats { FLAT_NAMES } else { SHARP_NAMES };
+        format!("{}{}", names[self.pitch() as usize], if self.minor { "m" } else { "" })
+    }
+
+    fn parse_musical(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        let pitch: i32 = match chars.next()?.to_ascii_uppercase() {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return None,
+        };
+
+        let rest = chars.as_str();
+        let (pitch, rest) = if let Some(rest) = rest.strip_prefix(['#', '♯']) {
+            (pitch + 1, rest)
+        } else if let Some(rest) = rest.strip_prefix(['b', '♭']) {
+            (pitch - 1, rest)
+        } else {
+            (pitch, rest)
+        };
+
+        let minor = match rest.trim() {
+            "" | "maj" | "major" | "Maj" | "Major" | "M" => false,
+            "m" | "min" | "minor" | "Min" | "Minor" => true,
+            _ => return None,
+        };
+
+        Some(Self::from_pitch(pitch, minor))
+    }
+}
+
+impl std::str::FromStr for CamelotKey {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if !s.starts_with(|c: char| c.is_ascii_digit()) {
+            return Self::parse_musical(s).ok_or("Invalid key");
+        }
+
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, mode) = s.split_at(split);
+        let number = number.parse::<u8>().map_err(|_| "Invalid key")?;
+        if !(1..=12).contains(&number) {
+            return Err("Invalid key");
+        }
+
+        match mode.trim().to_ascii_lowercase().as_str() {
+            "a" => Ok(CamelotKey { number, minor: true }),
+            "b" => Ok(CamelotKey { number, minor: false }),
+            // Open Key 1m/1d is camelot 8A/8B.
+            "m" => Ok(CamelotKey { number: (number + 6) % 12 + 1, minor: true }),
+            "d" => Ok(CamelotKey { number: (number + 6) % 12 + 1, minor: false }),
+            _ => Err("Invalid key"),
+        }
+    }
+}
+
+impl std::fmt::Display for CamelotKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.number, if self.minor { "A" } else { "B" })
+    }
+}
+
+/**
+* How keys are shown in the table and written to exports.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum KeyNotation {
+    #[default]
+    AsImported,
+    Camelot,
+    OpenKey,
+    MusicalSharps,
+    MusicalFlats,
+}
+
+impl KeyNotation {
+    pub const ALL: [KeyNotation; 5] = [
+        KeyNotation::AsImported,
+        KeyNotation::Camelot,
+        KeyNotation::OpenKey,
+        KeyNotation::MusicalSharps,
+        KeyNotation::MusicalFlats,
+    ];
+
+    /**
+     * Format a parsed key. Keys have no imported text, so that shows as camelot.
+     */
+    pub fn format(self, key: CamelotKey) -> String {
+        match self {
+            KeyNotation::AsImported | KeyNotation::Camelot => key.to_string(),
+            KeyNotation::OpenKey => key.to_open_key(),
+            KeyNotation::MusicalSharps => key.to_musical(false),
+            KeyNotation::MusicalFlats => key.to_musical(true),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            KeyNotation::AsImported => "As imported",
+            KeyNotation::Camelot => "Camelot",
+            KeyNotation::OpenKey => "Open Key",
+            KeyNotation::MusicalSharps => "Musical (♯)",
+            KeyNotation::MusicalFlats => "Musical (♭)",
+        }
+    }
+}
+
+/**
+* Stable identity of a track, derived from its resolved file path.
+*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrackId(u64);
+
+impl TrackId {
+    // FNV-1a, so ids (and cache file names) don't change between runs or builds.
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let mut hasher = Fnv1a::default();
+        hasher.update(bytes);
+        Self(hasher.finish())
+    }
+}
+
+/**
+ * FNV-1a hash, stable across runs and builds unlike the std hashers.
+ */
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for TrackId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize, Clone)]
 pub struct TrackMetadata {
-    #[serde(rename = "#")]
-    pub id: u32,
+    #[serde(skip_deserializing)]
+    pub id: TrackId,
+
+    // Row number from the source, for display only.
+    #[serde(rename = "#", default)]
+    pub number: u32,
 
     #[serde(rename = "Track Title")]
     pub title: String,
 
-    #[serde(rename = "Artist")]
+    #[serde(rename = "Artist", default)]
     pub artist: String,
 
-    #[serde(rename = "BPM")]
-    pub bpm: String,
+    #[serde(rename = "Album", default)]
+    pub album: String,
+
+    #[serde(rename = "BPM", default)]
+    pub bpm: Parsed<Bpm>,
 
-    #[serde(rename = "Key")]
-    pub key: String,
+    #[serde(rename = "Key", default)]
+    pub key: Parsed<CamelotKey>,
 
-    #[serde(rename = "Time")]
-    pub time: String,
+    #[serde(rename = "Time", default)]
+    pub time: Parsed<TrackTime>,
 
-    #[serde(rename = "Message")]
+    #[serde(rename = "Message", default)]
     pub message: String,
 
-    #[serde(rename = "Date Added")]
-    date_added: String,
+    #[serde(rename = "Date Added", default)]
+    pub date_added: Parsed<chrono::NaiveDate>,
 
-    #[serde(rename = "Genre")]
-    genre: String,
+    #[serde(rename = "Genre", default)]
+    pub genre: String,
 
-    #[serde(rename = "My Tag")]
+    #[serde(rename = "My Tag", default)]
     pub my_tag: String,
 
-    #[serde(skip_deserializing)]
+    // Only exported tables have a Location, otherwise it comes from the m3u8.
+    #[serde(rename = "Location", default, deserialize_with = "segment_from_location")]
     pub media_segment: m3u8_rs::MediaSegment,
+
+    // Exact length in seconds from our own exports, "Time" may be rounded.
+    #[serde(rename = "Duration", default)]
+    pub duration: Parsed<f32>,
+
+    // Name of the file, playlist or folder the track was imported from.
+    #[serde(skip_deserializing)]
+    pub source: String,
+}
+
+fn segment_from_location<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<m3u8_rs::MediaSegment, D::Error> {
+    use serde::Deserialize;
+    let location = String::deserialize(deserializer)?;
+
+    Ok(m3u8_rs::MediaSegment {
+        uri: crate::paths::uri_to_path(location.trim()),
+        ..Default::default()
+    })
+}
+
+impl TrackMetadata {
+    /**
+     * Derive the id from the resolved file path.
+     * Tracks without a file yet fall back to their row contents.
+     */
+    pub fn update_id(&mut self) {
+        self.id = if self.media_segment.uri.is_empty() {
+            TrackId::from_bytes(format!("{}\0{}\0{}\0{}", self.number, self.artist, self.title, self.time).as_bytes())
+        } else {
+            let path = std::path::Path::new(&self.media_segment.uri);
+            let resolved = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            TrackId::from_bytes(resolved.to_string_lossy().as_bytes())
+        };
+    }
+
+    /**
+     * The key in the given notation. Keys that didn't parse keep their text.
+     */
+    pub fn key_text(&self, notation: KeyNotation) -> String {
+        match (self.key.value, notation) {
+            (None, _) | (_, KeyNotation::AsImported) => self.key.raw.clone(),
+            (Some(key), notation) => notation.format(key),
+        }
+    }
+
+    /**
+     * The key for the Key column, followed by the key it sounds in at
+     * `target_bpm` when pitching changes it.
+     */
+    pub fn key_display(&self, notation: KeyNotation, target_bpm: Option<f32>) -> String {
+        let text = self.key_text(notation);
+        match (self.key.value, self.effective_key(target_bpm)) {
+            (Some(key), Some(effective)) if key != effective => format!("{} → {}", text, notation.format(effective)),
+            _ => text,
+        }
+    }
+
+    /**
+     * The key the track sounds in when played at `target_bpm` without key lock.
+     * Pitching by a factor moves the key by 12 * log2(factor) semitones, rounded
+     * to the nearest key.
+     */
+    pub fn effective_key(&self, target_bpm: Option<f32>) -> Option<CamelotKey> {
+        let key = self.key.value?;
+        match (target_bpm, self.bpm.value) {
+            (Some(target), Some(bpm)) if target > 0. && bpm.0 > 0. => {
+                let semitones = (12. * (target / bpm.0).log2()).round() as i32;
+                Some(key.transpose(semitones))
+            }
+            _ => Some(key),
+        }
+    }
+
+    /**
+     * Track length in seconds from the "Time" column.
+     */
+    pub fn duration_secs(&self) -> Option<f32> {
+        self.time.value.map(|t| t.0.as_secs_f32())
+    }
 }
 
+/**
+ * Format seconds the way the "Time" column shows them (mm:ss).
+ */
+pub fn format_time(secs: f32) -> String {
+    let secs = secs.max(0.).round() as u32;
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum KeyCompareResult {
     PerfectMatch,
     Compatible,
     MaybeCompatible,
-    Incopmatible,
+    Incompatible,
+}
+
+impl KeyCompareResult {
+    pub fn label(self) -> &'static str {
+        match self {
+            KeyCompareResult::PerfectMatch => "Perfect match",
+            KeyCompareResult::Compatible => "Compatible",
+            KeyCompareResult::MaybeCompatible => "Maybe compatible",
+            KeyCompareResult::Incompatible => "Incompatible",
+        }
+    }
 }
 
 pub fn color_from_key_compare(key: KeyCompareResult) -> Option<eframe::egui::Color32> {
@@ -46,40 +453,154 @@ pub fn color_from_key_compare(key: KeyCompareResult) -> Option<eframe::egui::Col
         KeyCompareResult::PerfectMatch => Some(eframe::egui::Color32::from_rgb(11, 75, 128)),
         KeyCompareResult::Compatible => Some(eframe::egui::Color32::from_rgb(19, 128, 11)),
         KeyCompareResult::MaybeCompatible => Some(eframe::egui::Color32::from_rgb(143, 119, 11)),
-        KeyCompareResult::Incopmatible => None,
+        KeyCompareResult::Incompatible => None,
     }
 }
 
 /** 
-* Compare keys in camelot format
+* Find the rule of the set that allows going from key `a` to key `b`.
+* No rule means the keys don't mix.
+*/
+pub fn compare_keys<'a>(
+    a: &CamelotKey,
+    b: &CamelotKey,
+    rules: &'a crate::harmonic_rules::RuleSet,
+) -> Option<&'a crate::harmonic_rules::KeyRule> {
+    rules.matching(a, b)
+}
+
+/**
+* Pitch needed to play a track at the tempo of another.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BpmMatch {
+    // Pitch change for the track in percent, positive is faster.
+    pub percent: f32,
+    // 1 for same tempo, 2 or 0.5 when the track is played at double or half time.
+    pub factor: f32,
+}
+
+/**
+* Pitch to apply to a track at `bpm` so it plays at `target`, also trying
+* double and half time (70 mixes with 140). Picks whichever needs the least pitch.
 */
-pub fn compare_keys(a: &str, b: &str) -> Result<KeyCompareResult, &'static str> {
-    if a == b {
-        return Ok(KeyCompareResult::PerfectMatch);
+pub fn compare_bpm(bpm: Bpm, target: Bpm) -> Option<BpmMatch> {
+    if target.0 <= 0. || bpm.0 <= 0. {
+        return None;
     }
 
-    let (a_key, a_mode) = a.split_at(a.len() - 1);
-    let (b_key, b_mode) = b.split_at(b.len() - 1);
+    [1., 2., 0.5]
+        .into_iter()
+        .map(|factor| BpmMatch {
+            percent: (target.0 / (bpm.0 * factor) - 1.) * 100.,
+            factor,
+        })
+        .min_by(|a, b| a.percent.abs().total_cmp(&b.percent.abs()))
+}
 
-    let key_distance = (a_key.parse::<i32>().unwrap() - b_key.parse::<i32>().unwrap()).abs();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    match key_distance {
-        0 => Ok(KeyCompareResult::Compatible),
-        1 | 2 => {
-            if a_mode == b_mode {
-                Ok(KeyCompareResult::Compatible)
-            } else {
-                Ok(KeyCompareResult::MaybeCompatible)
-            }
-        },
-        3 | 5 | 7 => {
-            if a_mode == b_mode {
-                Ok(KeyCompareResult::MaybeCompatible)
-            } else {
-                Ok(KeyCompareResult::Incopmatible)
-            }
-        },
-        _ => Ok(KeyCompareResult::Incopmatible)
+    fn key(s: &str) -> CamelotKey {
+        s.parse().unwrap_or_else(|e| panic!("{}: {}", s, e))
+    }
+
+    fn all_keys() -> impl Iterator<Item = CamelotKey> {
+        (1..=12).flat_map(|number| [true, false].map(|minor| CamelotKey { number, minor }))
+    }
+
+    #[test]
+    fn parses_camelot() {
+        assert_eq!(key("8A"), CamelotKey { number: 8, minor: true });
+        assert_eq!(key("12b"), CamelotKey { number: 12, minor: false });
+        for invalid in ["0A", "13B", "8C", "A8", ""] {
+            assert!(invalid.parse::<CamelotKey>().is_err(), "{}", invalid);
+        }
     }
-}
 
+    #[test]
+    fn parses_open_key() {
+        assert_eq!(key("1m"), key("8A"));
+        assert_eq!(key("1d"), key("8B"));
+        assert_eq!(key("6m"), key("1A"));
+        assert_eq!(key("12d"), key("7B"));
+    }
+
+    #[test]
+    fn parses_musical() {
+        assert_eq!(key("Am"), key("8A"));
+        assert_eq!(key("A minor"), key("8A"));
+        assert_eq!(key("C"), key("8B"));
+        assert_eq!(key("C major"), key("8B"));
+        assert_eq!(key("F#m"), key("11A"));
+        assert_eq!(key("Gbm"), key("11A"));
+        assert_eq!(key("Abm"), key("1A"));
+        assert_eq!(key("E♭m"), key("2A"));
+        assert_eq!(key("Db"), key("3B"));
+        assert!("H".parse::<CamelotKey>().is_err());
+    }
+
+    #[test]
+    fn notations_round_trip() {
+        for k in all_keys() {
+            assert_eq!(key(&k.to_string()), k);
+            assert_eq!(key(&k.to_open_key()), k);
+            assert_eq!(key(&k.to_musical(false)), k);
+            assert_eq!(key(&k.to_musical(true)), k);
+        }
+    }
+
+    #[test]
+    fn effective_key_follows_pitch() {
+        let track = TrackMetadata {
+            bpm: "120".to_string().into(),
+            key: "8A".to_string().into(),
+            ..Default::default()
+        };
+
+        assert_eq!(track.effective_key(None), Some(key("8A")));
+        assert_eq!(track.effective_key(Some(122.)), Some(key("8A")));
+        assert_eq!(track.effective_key(Some(124.)), Some(key("A#m")));
+        assert_eq!(track.effective_key(Some(127.14)), Some(key("A#m")));
+        assert_eq!(track.effective_key(Some(113.26)), Some(key("G#m")));
+        assert_eq!(track.effective_key(Some(240.)), Some(key("8A")));
+
+        assert_eq!(track.key_display(KeyNotation::Camelot, Some(124.)), "8A → 3A");
+        assert_eq!(track.key_display(KeyNotation::AsImported, Some(120.)), "8A");
+
+        let no_bpm = TrackMetadata {
+            key: "8A".to_string().into(),
+            ..Default::default()
+        };
+        assert_eq!(no_bpm.effective_key(Some(124.)), Some(key("8A")));
+    }
+
+    #[test]
+    fn bpm_pitch_is_for_the_track() {
+        let pitch = |bpm: f32, target: f32| compare_bpm(Bpm(bpm), Bpm(target)).unwrap();
+
+        let up = pitch(124., 128.);
+        assert!((up.percent - 3.2258).abs() < 0.001);
+        assert_eq!(up.factor, 1.);
+        assert!((pitch(128., 124.).percent + 3.125).abs() < 0.001);
+
+        let double = pitch(70., 140.);
+        assert_eq!((double.percent, double.factor), (0., 2.));
+        let half = pitch(172., 87.);
+        assert_eq!(half.factor, 0.5);
+        assert!((half.percent - 1.1628).abs() < 0.001);
+
+        assert_eq!(compare_bpm(Bpm(0.), Bpm(128.)), None);
+    }
+
+    #[test]
+    fn transposes_by_semitones() {
+        assert_eq!(key("8A").transpose(1), key("A#m"));
+        assert_eq!(key("8B").transpose(7), key("G"));
+        assert_eq!(key("8B").transpose(-1), key("B"));
+        for k in all_keys() {
+            assert_eq!(k.transpose(12), k);
+        }
+    }
+}
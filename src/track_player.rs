@@ -42,7 +42,13 @@ impl Player {
         }
 
         let path = std::path::Path::new(&self.track.as_ref().unwrap().media_segment.uri);
-        let file = std::fs::File::open(path).unwrap();
+        let file = match std::fs::File::open(path) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("Error opening track: {}", e);
+                return;
+            }
+        };
         match self.stream_handle.play_once(file) {
             Ok(s) => {
                 self.sink = Some(s);
@@ -98,7 +104,7 @@ impl Player {
     pub fn ui(&mut self, ui: &mut eframe::egui::Ui) {
         use eframe::egui;
         if let Some(track) = &self.track {
-            let filename = track.id.to_string() + track.title.as_str() + track.artist.as_str() + ".png";
+            let filename = track.id.to_string() + ".png";
             let path = self.data_dir.join(filename.clone());
 
             // draw waveform.
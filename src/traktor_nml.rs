@@ -0,0 +1,180 @@
+use crate::collection::{Collection, CollectionPlaylist};
+use crate::playlist::ImportError;
+use crate::track_metadata::TrackMetadata;
+
+// MUSICAL_KEY values 0-11 are major keys from C, 12-23 the minor keys from Cm.
+const KEY_NAMES: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+/**
+ * Ask for a Traktor collection.nml file and parse it.
+ */
+pub fn pick() -> Option<Result<Collection, ImportError>> {
+    rfd::FileDialog::new()
+        .add_filter("Traktor NML", &["nml"])
+        .set_directory("/")
+        .pick_file()
+        .map(|path| {
+            let text = std::fs::read_to_string(&path)?;
+            parse(path, &text)
+        })
+}
+
+/**
+ * Parse a Traktor collection.
+ * Tracks are keyed by VOLUME + DIR + FILE, which is what playlist PRIMARYKEYs refer to.
+ */
+pub fn parse(path: std::path::PathBuf, text: &str) -> Result<Collection, ImportError> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| ImportError::Xml(e.to_string()))?;
+    let root = doc.root_element();
+    if !root.has_tag_name("NML") {
+        return Err(ImportError::Xml("Not a Traktor collection, missing NML.".to_string()));
+    }
+
+    let mut tracks = std::collections::HashMap::new();
+    let collection = root.children().find(|n| n.has_tag_name("COLLECTION"));
+    for (i, entry) in collection
+        .iter()
+        .flat_map(|c| c.children())
+        .filter(|n| n.has_tag_name("ENTRY"))
+        .enumerate()
+    {
+        let child = |name: &str| entry.children().find(|n| n.has_tag_name(name));
+        let child_attr = |name: &str, attr: &str| {
+            child(name)
+                .and_then(|n| n.attribute(attr))
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let Some(location) = child("LOCATION") else {
+            continue;
+        };
+        let volume = location.attribute("VOLUME").unwrap_or_default();
+        let dir = location.attribute("DIR").unwrap_or_default();
+        let file = location.attribute("FILE").unwrap_or_default();
+
+        let duration = child_attr("INFO", "PLAYTIME_FLOAT")
+            .parse::<f32>()
+            .or_else(|_| child_attr("INFO", "PLAYTIME").parse::<f32>())
+            .unwrap_or(0.);
+        let title = entry.attribute("TITLE").unwrap_or_default().to_string();
+        let artist = entry.attribute("ARTIST").unwrap_or_default().to_string();
+
+        let track = TrackMetadata {
+            number: i as u32 + 1,
+            title: title.clone(),
+            artist: artist.clone(),
+            album: child_attr("ALBUM", "TITLE"),
+            bpm: child_attr("TEMPO", "BPM")
+                .parse::<f32>()
+                .map_or(String::new(), |bpm| format!("{:.2}", bpm))
+                .into(),
+            key: key_name(&child_attr("MUSICAL_KEY", "VALUE")).into(),
+            time: crate::track_metadata::format_time(duration).into(),
+            message: child_attr("INFO", "COMMENT"),
+            date_added: format_date(&child_attr("INFO", "IMPORT_DATE")).into(),
+            genre: child_attr("INFO", "GENRE"),
+            media_segment: m3u8_rs::MediaSegment {
+                uri: file_path(volume, dir, file),
+                duration,
+                title: Some(format!("{} - {}", artist, title)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        tracks.insert(format!("{}{}{}", volume, dir, file), track);
+    }
+
+    let mut playlists = vec![];
+    if let Some(root_node) = root
+        .children()
+        .find(|n| n.has_tag_name("PLAYLISTS"))
+        .and_then(|p| p.children().find(|n| n.has_tag_name("NODE")))
+    {
+        collect_playlists(root_node, "", &mut playlists);
+    }
+
+    Ok(Collection::new(path, tracks, playlists))
+}
+
+/**
+ * Walk FOLDER nodes (children live under SUBNODES) and collect PLAYLIST nodes.
+ */
+fn collect_playlists(node: roxmltree::Node, parent: &str, playlists: &mut Vec<CollectionPlaylist>) {
+    let children = node
+        .children()
+        .filter(|n| n.has_tag_name("SUBNODES"))
+        .flat_map(|n| n.children())
+        .filter(|n| n.has_tag_name("NODE"));
+
+    for child in children {
+        let name = child.attribute("NAME").unwrap_or_default();
+        let full_name = if parent.is_empty() {
+            name.to_string()
+        } else {
+            format!("{} / {}", parent, name)
+        };
+
+        match child.attribute("TYPE") {
+            Some("FOLDER") => collect_playlists(child, &full_name, playlists),
+            Some("PLAYLIST") => {
+                let track_keys = child
+                    .children()
+                    .filter(|n| n.has_tag_name("PLAYLIST"))
+                    .flat_map(|n| n.children())
+                    .filter(|n| n.has_tag_name("ENTRY"))
+                    .filter_map(|n| n.children().find(|n| n.has_tag_name("PRIMARYKEY")))
+                    .filter_map(|n| n.attribute("KEY"))
+                    .map(|key| key.to_string())
+                    .collect();
+
+                playlists.push(CollectionPlaylist {
+                    name: full_name,
+                    track_keys,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn key_name(value: &str) -> String {
+    match value.parse::<usize>() {
+        Ok(v) if v < 12 => KEY_NAMES[v].to_string(),
+        Ok(v) if v < 24 => format!("{}m", KEY_NAMES[v - 12]),
+        _ => String::new(),
+    }
+}
+
+/**
+ * Build a file path from Traktor's LOCATION parts.
+ * DIR uses "/:" as separator, VOLUME is a drive letter on windows
+ * and a volume name on macOS.
+ */
+fn file_path(volume: &str, dir: &str, file: &str) -> String {
+    let dir = dir.replace("/:", "/");
+
+    if volume.ends_with(':') {
+        return format!("{}{}{}", volume, dir, file);
+    }
+
+    // The system volume is mounted at "/", others live under /Volumes.
+    let external = format!("/Volumes/{}{}{}", volume, dir, file);
+    if !volume.is_empty() && std::path::Path::new(&external).exists() {
+        external
+    } else {
+        format!("{}{}", dir, file)
+    }
+}
+
+/**
+ * Traktor writes "2021/3/4", the rest of the app uses "2021-03-04".
+ */
+fn format_date(date: &str) -> String {
+    let parts: Vec<u32> = date.split('/').filter_map(|p| p.parse().ok()).collect();
+    match parts[..] {
+        [year, month, day] => format!("{:04}-{:02}-{:02}", year, month, day),
+        _ => date.to_string(),
+    }
+}
@@ -0,0 +1,72 @@
+/*!
+*
+* VirtualDJ playlists (.vdjfolder).
+*
+*/
+use crate::path_rules::PathProfile;
+use crate::track_metadata::{KeyNotation, TrackMetadata};
+use crate::xml::escape;
+
+/**
+ * Ask for a file name and write the tracks as a VirtualDJ playlist.
+ */
+pub fn export(tracks: &[TrackMetadata], profile: Option<&PathProfile>, notation: KeyNotation) -> std::io::Result<()> {
+    let file = rfd::FileDialog::new()
+        .add_filter("VirtualDJ playlist", &["vdjfolder"])
+        .set_directory("/")
+        .save_file();
+
+    let Some(file) = file else {
+        return Ok(());
+    };
+
+    let mut f = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&file)?;
+    write_to(tracks, profile, notation, &file, &mut f)
+}
+
+/**
+ * Write a VirtualFolder with one song per track, in table order.
+ * VirtualDJ stores bpm as seconds per beat.
+ */
+pub fn write_to<T: std::io::Write>(
+    tracks: &[TrackMetadata],
+    profile: Option<&PathProfile>,
+    notation: KeyNotation,
+    playlist_path: &std::path::Path,
+    w: &mut T,
+) -> std::io::Result<()> {
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, r#"<VirtualFolder noDuplicates="no">"#)?;
+
+    for (i, track) in tracks.iter().enumerate() {
+        let uri = &track.media_segment.uri;
+        let path = profile.map_or(uri.clone(), |profile| profile.apply(uri, playlist_path));
+        let size = std::fs::metadata(uri).map_or(0, |m| m.len());
+        let duration = track.duration_secs().unwrap_or(track.media_segment.duration);
+        let bpm = track
+            .bpm
+            .value
+            .filter(|bpm| bpm.0 > 0.)
+            .map_or(String::new(), |bpm| format!("{:.6}", 60. / bpm.0));
+
+        writeln!(
+            w,
+            r#"  <song path="{}" size="{}" songlength="{:.3}" bpm="{}" key="{}" artist="{}" title="{}" idx="{}" />"#,
+            escape(&path),
+            size,
+            duration,
+            bpm,
+            escape(&track.key_text(notation)),
+            escape(&track.artist),
+            escape(&track.title),
+            i,
+        )?;
+    }
+
+    writeln!(w, "</VirtualFolder>")?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+/*!
+*
+* XSPF ("spiff") playlists, the XML format streaming tools use.
+*
+*/
+use crate::path_rules::PathProfile;
+use crate::playlist::ImportError;
+use crate::track_metadata::TrackMetadata;
+use crate::xml::escape;
+
+/**
+ * Read the trackList of an XSPF file. Relative locations are resolved
+ * against the folder of `path`.
+ */
+pub fn parse(path: &std::path::Path, text: &str) -> Result<Vec<TrackMetadata>, ImportError> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| ImportError::Xml(e.to_string()))?;
+    let root = doc.root_element();
+    if !root.has_tag_name("playlist") {
+        return Err(ImportError::Xml("Not an XSPF playlist, missing playlist element.".to_string()));
+    }
+
+    let playlist_dir = path.parent().unwrap_or(std::path::Path::new(""));
+    let tracks = root
+        .children()
+        .filter(|n| n.has_tag_name("trackList"))
+        .flat_map(|n| n.children())
+        .filter(|n| n.has_tag_name("track"))
+        .enumerate()
+        .map(|(i, node)| {
+            let text = |name: &str| {
+                node.children()
+                    .find(|n| n.has_tag_name(name))
+                    .and_then(|n| n.text())
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string()
+            };
+
+            // Duration is in milliseconds.
+            let duration = text("duration").parse::<f32>().map_or(0., |ms| ms / 1000.);
+            let title = text("title");
+            let artist = text("creator");
+
+            let mut track = TrackMetadata {
+                number: i as u32 + 1,
+                time: if duration > 0. { crate::track_metadata::format_time(duration) } else { String::new() }.into(),
+                album: text("album"),
+                message: text("annotation"),
+                media_segment: m3u8_rs::MediaSegment {
+                    uri: crate::paths::resolve(&text("location"), playlist_dir),
+                    duration,
+                    title: Some(format!("{} - {}", artist, title)),
+                    ..Default::default()
+                },
+                title,
+                artist,
+                ..Default::default()
+            };
+            track.update_id();
+            track
+        })
+        .collect();
+
+    Ok(tracks)
+}
+
+/**
+ * Write an XSPF playlist. Paths are rewritten with the given profile
+ * for the playlist at `playlist_path`, then written as URIs.
+ */
+pub fn write_to<T: std::io::Write>(
+    tracks: &[TrackMetadata],
+    name: &str,
+    profile: Option<&PathProfile>,
+    playlist_path: &std::path::Path,
+    w: &mut T,
+) -> std::io::Result<()> {
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, r#"<playlist version="1" xmlns="http://xspf.org/ns/0/">"#)?;
+    writeln!(w, "  <title>{}</title>", escape(name))?;
+    writeln!(w, "  <trackList>")?;
+
+    for track in tracks {
+        let uri = &track.media_segment.uri;
+        let location = profile.map_or(uri.clone(), |profile| profile.apply(uri, playlist_path));
+        let duration = track.duration_secs().unwrap_or(track.media_segment.duration);
+
+        writeln!(w, "    <track>")?;
+        writeln!(w, "      <location>{}</location>", escape(&crate::paths::path_to_uri_reference(&location)))?;
+        for (element, value) in [
+            ("title", &track.title),
+            ("creator", &track.artist),
+            ("album", &track.album),
+            ("annotation", &track.message),
+        ] {
+            if !value.is_empty() {
+                writeln!(w, "      <{0}>{1}</{0}>", element, escape(value))?;
+            }
+        }
+        if duration > 0. {
+            writeln!(w, "      <duration>{}</duration>", (duration * 1000.).round() as u64)?;
+        }
+        writeln!(w, "    </track>")?;
+    }
+
+    writeln!(w, "  </trackList>")?;
+    writeln!(w, "</playlist>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    #[test]
+    fn reads_track_list() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <trackList>
+    <track>
+      <location>file:///music/My%20Track.flac</location>
+      <title>My Track</title>
+      <creator>Artist</creator>
+      <album>Album</album>
+      <duration>312456</duration>
+    </track>
+    <track>
+      <location>relative/b.mp3</location>
+    </track>
+  </trackList>
+</playlist>"#;
+        let tracks = parse(std::path::Path::new("/lists/set.xspf"), text).unwrap();
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].media_segment.uri, "/music/My Track.flac");
+        assert_eq!(tracks[0].title, "My Track");
+        assert_eq!(tracks[0].artist, "Artist");
+        assert_eq!(tracks[0].album, "Album");
+        assert_eq!(tracks[0].media_segment.duration, 312.456);
+        assert_eq!(tracks[1].media_segment.uri, "/lists/relative/b.mp3");
+    }
+
+    #[test]
+    fn rejects_other_xml() {
+        assert!(parse(std::path::Path::new("set.xspf"), "<DJ_PLAYLISTS/>").is_err());
+    }
+}